@@ -0,0 +1,95 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A time of day in the user's local timezone.
+#[derive(
+    bincode::Decode,
+    bincode::Encode,
+    serde::Serialize,
+    serde::Deserialize,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Debug,
+)]
+pub struct LocalTime {
+    pub hour: u8,
+    pub minute: u8,
+}
+
+impl LocalTime {
+    /// The local time of day at the given time.
+    pub fn at(time: SystemTime) -> Self {
+        let secs = match time.duration_since(UNIX_EPOCH) {
+            Ok(since) => since.as_secs() as libc::time_t,
+            Err(before) => -(before.duration().as_secs() as libc::time_t),
+        };
+
+        // SAFETY: `tm` is plain data that is fully written by `localtime_r`, which is thread safe.
+        let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+        if unsafe { libc::localtime_r(&secs, &mut tm) }.is_null() {
+            // Fall back to UTC if the local time cannot be found.
+            let day_secs = secs.rem_euclid(24 * 60 * 60);
+            return Self {
+                hour: (day_secs / 3600) as u8,
+                minute: (day_secs / 60 % 60) as u8,
+            };
+        }
+
+        Self {
+            hour: tm.tm_hour as u8,
+            minute: tm.tm_min as u8,
+        }
+    }
+}
+
+impl std::fmt::Display for LocalTime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:0>2}:{:0>2}", self.hour, self.minute)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use super::LocalTime;
+
+    unsafe extern "C" {
+        /// Reads the timezone from the environment again.
+        fn tzset();
+    }
+
+    #[test]
+    fn time_of_day() {
+        // SAFETY: No other test changes the environment, & other tests only read it through glibc's `localtime_r`,
+        // which keeps a copy of the timezone.
+        unsafe {
+            std::env::set_var("TZ", "UTC");
+            tzset();
+        }
+
+        // 2023-11-14 22:13:20 UTC.
+        let time = LocalTime::at(UNIX_EPOCH + Duration::from_secs(1_700_000_000));
+        assert_eq!(
+            time,
+            LocalTime {
+                hour: 22,
+                minute: 13
+            }
+        );
+        assert_eq!(time.to_string(), "22:13");
+
+        // Times before the epoch are still on the previous day.
+        let time = LocalTime::at(UNIX_EPOCH - Duration::from_secs(90));
+        assert_eq!(
+            time,
+            LocalTime {
+                hour: 23,
+                minute: 58
+            }
+        );
+    }
+}
@@ -1,6 +1,9 @@
 use std::future::Future;
 
-use super::{BINCODE_CONF, BincodeConfiguration};
+use super::{
+    BINCODE_CONF, BincodeConfiguration,
+    capture::{self, Direction},
+};
 use bincode::{
     Decode, Encode,
     error::{DecodeError, EncodeError},
@@ -37,30 +40,29 @@ impl<From> AsyncReadObj for From
 where
     From: AsyncReadExt + Unpin,
 {
-    fn read_obj<Obj>(&mut self) -> impl Future<Output = Result<Obj, AsyncReadError>>
+    async fn read_obj<Obj>(&mut self) -> Result<Obj, AsyncReadError>
     where
         Obj: Decode<BincodeConfiguration>,
     {
-        async {
-            let buf = &mut [0; (usize::BITS / 8) as usize];
-            self.read_exact(buf).await?;
-            let len = usize::from_ne_bytes(*buf);
-            log::trace!("Async Read Len: {len}");
-
-            let mut buf = vec![0u8; len].into_boxed_slice();
-
-            let read_exact = self.read_exact(&mut buf).await?;
-            if read_exact != len as usize {
-                return Err(AsyncReadError::BufferMissMatch {
-                    expected: len,
-                    read: read_exact,
-                });
-            }
+        let buf = &mut [0; (usize::BITS / 8) as usize];
+        self.read_exact(buf).await?;
+        let len = usize::from_ne_bytes(*buf);
+        log::trace!("Async Read Len: {len}");
+
+        let mut buf = vec![0u8; len].into_boxed_slice();
+
+        let read_exact = self.read_exact(&mut buf).await?;
+        if read_exact != len {
+            return Err(AsyncReadError::BufferMissMatch {
+                expected: len,
+                read: read_exact,
+            });
+        }
 
-            log::trace!("Async Read Data: {:?}", buf);
+        log::trace!("Async Read Data: {:?}", buf);
+        capture::record(Direction::Received, &buf);
 
-            Ok(bincode::decode_from_slice_with_context(&buf, BINCODE_CONF, BINCODE_CONF)?.0)
-        }
+        Ok(bincode::decode_from_slice_with_context(&buf, BINCODE_CONF, BINCODE_CONF)?.0)
     }
 }
 
@@ -92,21 +94,20 @@ impl<To> AsyncWriteObj for To
 where
     To: AsyncWriteExt + Unpin,
 {
-    fn write_obj<Obj>(&mut self, data: Obj) -> impl Future<Output = Result<(), AsyncWriteError>>
+    async fn write_obj<Obj>(&mut self, data: Obj) -> Result<(), AsyncWriteError>
     where
         Obj: Encode,
     {
-        async {
-            let data = bincode::encode_to_vec(data, BINCODE_CONF)?;
-            log::trace!("Async Write Len: {}", data.len());
-            log::trace!("Async Write Data: {:?}", data);
+        let data = bincode::encode_to_vec(data, BINCODE_CONF)?;
+        log::trace!("Async Write Len: {}", data.len());
+        log::trace!("Async Write Data: {:?}", data);
+        capture::record(Direction::Sent, &data);
 
-            self.write_all(&data.len().to_ne_bytes()).await?;
-            self.write_all(data.as_slice()).await?;
+        self.write_all(&data.len().to_ne_bytes()).await?;
+        self.write_all(data.as_slice()).await?;
 
-            self.flush().await?;
-            Ok(())
-        }
+        self.flush().await?;
+        Ok(())
     }
 }
 
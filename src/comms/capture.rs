@@ -0,0 +1,206 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufReader, ErrorKind, Read, Write},
+    path::{Path, PathBuf},
+    sync::{Mutex, OnceLock},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bincode::{
+    Decode, Encode,
+    error::{DecodeError, EncodeError},
+};
+
+use super::BINCODE_CONF;
+
+/// The capture of the current process, if capturing is enabled.
+static CAPTURE: OnceLock<Mutex<Capture>> = OnceLock::new();
+
+/// Which side of the connection a frame was recorded on.
+#[derive(Decode, Encode, Clone, Copy, PartialEq, Debug, clap::ValueEnum)]
+pub enum Endpoint {
+    Tray,
+    Gui,
+}
+
+/// Whether a frame was sent or received by the recording [`Endpoint`].
+#[derive(Decode, Encode, Clone, Copy, PartialEq, Debug)]
+pub enum Direction {
+    Sent,
+    Received,
+}
+
+/// A single frame recorded in a capture.
+#[derive(Decode, Encode, Clone, PartialEq, Debug)]
+pub struct Frame {
+    /// Microseconds since the unix epoch at which the frame was recorded.
+    pub timestamp: u64,
+    /// The side of the connection that recorded the frame.
+    pub endpoint: Endpoint,
+    /// Whether the frame was sent or received by the [`endpoint`](Self::endpoint).
+    pub direction: Direction,
+    /// The encoded frame, without its length prefix.
+    pub data: Vec<u8>,
+}
+
+/// An error encountered when writing a [`Frame`] to a capture.
+#[derive(thiserror::Error, Debug)]
+pub enum CaptureError {
+    /// Unable to encode the frame.
+    #[error("Unable to encode frame.")]
+    Encode(#[from] EncodeError),
+    /// Unable to write the frame to the capture file.
+    #[error("Unable to write frame.")]
+    Write(#[from] std::io::Error),
+}
+
+/// An error encountered when reading [`Frame`]s from a capture.
+#[derive(thiserror::Error, Debug)]
+pub enum LoadError {
+    /// Unable to read the capture file.
+    #[error("Unable to read capture.")]
+    Read(#[from] std::io::Error),
+    /// The capture file contains invalid data.
+    #[error("Capture contains invalid data.")]
+    Decode(#[from] DecodeError),
+}
+
+/// Records the frames sent & received by one [`Endpoint`] to a file.
+///
+/// Both the tray & the GUI can append to the same file, as each frame is written with a single write.
+pub struct Capture {
+    file: File,
+    path: PathBuf,
+    endpoint: Endpoint,
+}
+
+impl Capture {
+    /// Opens the capture file at the given path for appending, creating it if it does not exist.
+    pub fn open(path: &Path, endpoint: Endpoint) -> std::io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+
+        Ok(Self {
+            file,
+            path: path.to_owned(),
+            endpoint,
+        })
+    }
+
+    /// Appends a frame with the given data to the capture.
+    pub fn write(&mut self, direction: Direction, data: &[u8]) -> Result<(), CaptureError> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_micros() as u64;
+
+        let frame = Frame {
+            timestamp,
+            endpoint: self.endpoint,
+            direction,
+            data: data.to_vec(),
+        };
+
+        let data = bincode::encode_to_vec(frame, BINCODE_CONF)?;
+        let mut buf = Vec::with_capacity(size_of::<usize>() + data.len());
+        buf.extend_from_slice(&data.len().to_ne_bytes());
+        buf.extend_from_slice(&data);
+
+        self.file.write_all(&buf)?;
+        Ok(())
+    }
+}
+
+/// Starts recording all frames sent & received by this process to the given file.
+///
+/// This can only be started once, subsequent calls are ignored.
+pub fn start(path: &Path, endpoint: Endpoint) -> std::io::Result<()> {
+    let capture = Capture::open(path, endpoint)?;
+    if CAPTURE.set(Mutex::new(capture)).is_err() {
+        log::warn!("Capture was already started.");
+    }
+    Ok(())
+}
+
+/// The file frames are being captured to, if capturing is enabled.
+pub fn path() -> Option<PathBuf> {
+    let capture = CAPTURE.get()?.lock().ok()?;
+    Some(capture.path.clone())
+}
+
+/// Records a frame if capturing is enabled.
+pub(super) fn record(direction: Direction, data: &[u8]) {
+    let Some(capture) = CAPTURE.get() else {
+        return;
+    };
+
+    let Ok(mut capture) = capture.lock() else {
+        return;
+    };
+
+    if let Err(err) = capture.write(direction, data) {
+        log::error!("Unable to capture frame: {err}");
+    }
+}
+
+/// Reads all the frames stored in the capture file at the given path.
+pub fn load(path: &Path) -> Result<Vec<Frame>, LoadError> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut frames = Vec::new();
+
+    loop {
+        let buf = &mut [0; size_of::<usize>()];
+        match reader.read_exact(buf) {
+            Ok(()) => {}
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof => break,
+            Err(err) => return Err(err.into()),
+        }
+
+        let mut buf = vec![0; usize::from_ne_bytes(*buf)].into_boxed_slice();
+        reader.read_exact(&mut buf)?;
+
+        frames.push(bincode::decode_from_slice_with_context(&buf, BINCODE_CONF, BINCODE_CONF)?.0);
+    }
+
+    Ok(frames)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Capture, Direction, Endpoint, load};
+
+    #[test]
+    fn write_load() {
+        let file = tempfile::NamedTempFile::new().expect("Can create temp file");
+
+        let mut tray = Capture::open(file.path(), Endpoint::Tray).expect("Can open capture");
+        let mut gui = Capture::open(file.path(), Endpoint::Gui).expect("Can open capture");
+
+        tray.write(Direction::Sent, &[0, 0, 0, 1])
+            .expect("Can write frame");
+        gui.write(Direction::Received, &[0, 0, 0, 1])
+            .expect("Can write frame");
+        gui.write(Direction::Sent, &[0, 0, 0, 0])
+            .expect("Can write frame");
+
+        let frames = load(file.path()).expect("Can load capture");
+        assert_eq!(frames.len(), 3);
+
+        assert_eq!(frames[0].endpoint, Endpoint::Tray);
+        assert_eq!(frames[0].direction, Direction::Sent);
+        assert_eq!(frames[0].data, [0, 0, 0, 1]);
+
+        assert_eq!(frames[1].endpoint, Endpoint::Gui);
+        assert_eq!(frames[1].direction, Direction::Received);
+
+        assert_eq!(frames[2].direction, Direction::Sent);
+        assert_eq!(frames[2].data, [0, 0, 0, 0]);
+
+        assert!(frames.is_sorted_by_key(|frame| frame.timestamp));
+    }
+
+    #[test]
+    fn load_empty() {
+        let file = tempfile::NamedTempFile::new().expect("Can create temp file");
+        assert!(load(file.path()).expect("Can load capture").is_empty());
+    }
+}
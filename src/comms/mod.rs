@@ -1,22 +1,67 @@
-use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    path::PathBuf,
+    time::Duration,
+};
 
 use bincode::{
     Decode, Encode,
     config::{self, Configuration},
 };
+use timer_sync::{SyncMessage, TimerCommand};
+
+use crate::{dnd::DoNotDisturb, format::TimeFormat, shortcuts::Shortcuts, timer::Preset};
 
 pub mod async_socket;
+pub mod capture;
+pub mod replay;
 pub mod sync_socket;
+pub mod timer_sync;
+pub mod transport;
 
 pub const SOCKET_ADDR: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 23408);
 
+/// The unix domain socket used with [`TransportKind::Unix`].
+///
+/// This is placed in the user's runtime directory when available.
+pub fn socket_path() -> PathBuf {
+    std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir)
+        .join(concat!(env!("CARGO_PKG_NAME"), ".sock"))
+}
+
+/// How the GUI is connected to the tray.
+#[derive(Clone, Copy, PartialEq, Debug, Default, clap::ValueEnum)]
+pub enum TransportKind {
+    /// The GUI connects to the tray on [`SOCKET_ADDR`].
+    #[default]
+    Tcp,
+    /// The GUI connects to the tray on the unix domain socket at [`socket_path`].
+    Unix,
+    /// The tray passes one end of a socket pair to the GUI it spawns as its stdin.
+    Pipe,
+}
+
 /// Actions to be performed by the timer GUI.
-#[derive(Decode, Encode, PartialEq, Debug)]
+#[derive(Decode, Encode, Clone, PartialEq, Debug)]
 pub enum GuiAction {
     /// Close the GUI and send confirmation to the tray.
     Close,
-    /// Close the GUI **without** sending confirmation to the tray.
+    /// Save & close the GUI as the tray is quitting, then send confirmation to the tray.
     Quit,
+    /// Update the GUI's copy of the timers.
+    Sync(SyncMessage),
+    /// The presets that timers can be started from.
+    Presets(Vec<Preset>),
+    /// When the user is not alerted that timers have finished.
+    DoNotDisturb(DoNotDisturb),
+    /// The keys the user would like to trigger each keyboard shortcut.
+    Shortcuts(Shortcuts),
+    /// How times are shown, unless a timer has its own format.
+    TimeFormat(TimeFormat),
+    /// How long the timers that have been removed counted past their ends.
+    RemovedOverrun(Duration),
 }
 
 /// Actions that have been performed by the timer GUI.
@@ -24,6 +69,24 @@ pub enum GuiAction {
 pub enum GuiResponse {
     Opened,
     Closed,
+    /// The GUI missed a [`SyncMessage`] & needs a new snapshot.
+    Resync,
+    /// The user requested a change to the timers.
+    Timer(TimerCommand),
+    /// The user changed the presets that timers can be started from.
+    Presets(Vec<Preset>),
+    /// The user changed when they are not alerted that timers have finished.
+    DoNotDisturb(DoNotDisturb),
+    /// The user changed the keys they would like to trigger each keyboard shortcut.
+    Shortcuts(Shortcuts),
+    /// The user changed how times are shown, unless a timer has its own format.
+    TimeFormat(TimeFormat),
+    /// The user chose to forget how long every timer has counted past its end, including those that were removed.
+    ForgetOverrun,
+    /// The GUI stopped unexpectedly, with the reason why.
+    ///
+    /// This is sent by the GUI when it panics, or by the tray itself when the connection to the GUI fails.
+    Crashed(String),
 }
 
 /// A type alias for the bincode configuration used in this codebase.
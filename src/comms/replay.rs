@@ -0,0 +1,254 @@
+use std::{
+    io::ErrorKind,
+    os::{fd::OwnedFd, unix::net::UnixStream},
+    path::Path,
+    process::{Child, Command, Stdio},
+    time::Duration,
+};
+
+use tokio::{
+    io::{AsyncReadExt as _, AsyncWriteExt as _},
+    sync::mpsc::UnboundedSender,
+};
+
+use super::{
+    SOCKET_ADDR, TransportKind,
+    capture::{self, Direction, Endpoint, Frame, LoadError},
+    socket_path,
+    transport::{Listener as _, PipeTransport, TcpTransport, Transport, UnixTransport},
+};
+
+/// An error encountered when replaying a capture.
+#[derive(thiserror::Error, Debug)]
+pub enum ReplayError {
+    /// Unable to load the capture.
+    #[error(transparent)]
+    Load(#[from] LoadError),
+    /// Unable to connect to the endpoint being replayed into.
+    #[error("Unable to connect to the {0:?}.")]
+    Connect(Endpoint, #[source] std::io::Error),
+    /// Unable to send a frame to the endpoint being replayed into.
+    #[error("Unable to send frame.")]
+    Send(#[source] std::io::Error),
+}
+
+/// Feeds the frames in the capture at the given path into the `target`, keeping the original timing between frames.
+///
+/// When replaying into the tray this connects to a running tray in place of the GUI.
+/// When replaying into the GUI this waits for a GUI to connect in place of the tray, spawning one when using
+/// [`TransportKind::Pipe`].
+pub fn replay(path: &Path, target: Endpoint, transport: TransportKind) -> Result<(), ReplayError> {
+    let frames = frames_for(capture::load(path)?, target);
+    log::info!(
+        "Replaying {} frames into {target:?} over {transport:?}",
+        frames.len()
+    );
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|err| ReplayError::Connect(target, err))?;
+
+    runtime.block_on(async {
+        match transport {
+            TransportKind::Tcp => replay_over(TcpTransport::new(SOCKET_ADDR), frames, target).await,
+            TransportKind::Unix => {
+                replay_over(UnixTransport::new(socket_path()), frames, target).await
+            }
+            // A tray using pipes only talks to the GUIs it spawns, which replaying as a GUI also has to be.
+            TransportKind::Pipe => {
+                let (transport, pipes) = PipeTransport::new();
+                if target == Endpoint::Gui {
+                    spawn_gui(pipes).map_err(|err| ReplayError::Connect(target, err))?;
+                }
+                replay_over(transport, frames, target).await
+            }
+        }
+    })
+}
+
+/// Feeds the frames into the `target`, connected to with the given transport.
+async fn replay_over<T: Transport>(
+    transport: T,
+    frames: Vec<Frame>,
+    target: Endpoint,
+) -> Result<(), ReplayError> {
+    let stream = match target {
+        Endpoint::Tray => transport.connect().await,
+        Endpoint::Gui => match transport.listen().await {
+            Ok(mut listener) => {
+                log::info!("Waiting for GUI to connect");
+                listener.accept().await
+            }
+            Err(err) => Err(err),
+        },
+    }
+    .map_err(|err| ReplayError::Connect(target, err))?;
+    let (mut reader, mut writer) = T::split(stream);
+
+    // Drain responses so the target never blocks on writing.
+    let drain = tokio::spawn(async move {
+        loop {
+            let buf = &mut [0; size_of::<usize>()];
+            let read = match reader.read_exact(buf).await {
+                Ok(_) => {
+                    let mut buf = vec![0; usize::from_ne_bytes(*buf)];
+                    reader.read_exact(&mut buf).await.map(|_| buf)
+                }
+                Err(err) => Err(err),
+            };
+
+            match read {
+                Ok(data) => log::debug!("{target:?} responded: {data:?}"),
+                Err(err) if err.kind() == ErrorKind::UnexpectedEof => return,
+                Err(err) => {
+                    log::error!("Unable to read from {target:?}: {err}");
+                    return;
+                }
+            }
+        }
+    });
+
+    let mut last_timestamp = frames.first().map(|frame| frame.timestamp);
+    for frame in frames {
+        if let Some(last) = last_timestamp {
+            tokio::time::sleep(Duration::from_micros(frame.timestamp.saturating_sub(last))).await;
+        }
+        last_timestamp = Some(frame.timestamp);
+
+        log::debug!("Replaying: {:?}", frame.data);
+        let sent = async {
+            writer.write_all(&frame.data.len().to_ne_bytes()).await?;
+            writer.write_all(&frame.data).await?;
+            writer.flush().await
+        };
+        sent.await.map_err(ReplayError::Send)?;
+    }
+
+    drain.abort();
+    Ok(())
+}
+
+/// Spawns a GUI connected to one end of a socket pair, passing the other end to the [`PipeTransport`].
+fn spawn_gui(pipes: UnboundedSender<UnixStream>) -> std::io::Result<Child> {
+    let (replay_end, gui_end) = UnixStream::pair()?;
+    let gui = Command::new(std::env::current_exe()?)
+        .args(["--gui", "--transport", "pipe"])
+        .stdin(Stdio::from(OwnedFd::from(gui_end)))
+        .spawn()?;
+
+    pipes
+        .send(replay_end)
+        .map_err(|_| std::io::Error::new(ErrorKind::BrokenPipe, "Not listening for the GUI"))?;
+    Ok(gui)
+}
+
+/// Selects the frames that were received by the `target`, in the order they were recorded.
+///
+/// The frames sent by the opposite endpoint are preferred, with the frames recorded by the `target` itself used when
+/// the opposite endpoint was not captured.
+fn frames_for(mut frames: Vec<Frame>, target: Endpoint) -> Vec<Frame> {
+    frames.sort_by_key(|frame| frame.timestamp);
+
+    let sent_to_target =
+        |frame: &Frame| frame.endpoint != target && frame.direction == Direction::Sent;
+    let received_by_target =
+        |frame: &Frame| frame.endpoint == target && frame.direction == Direction::Received;
+
+    if frames.iter().any(sent_to_target) {
+        frames.retain(sent_to_target);
+    } else {
+        frames.retain(received_by_target);
+    }
+
+    frames
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{frames_for, replay_over};
+    use crate::comms::{
+        BINCODE_CONF, GuiAction,
+        async_socket::AsyncReadObj as _,
+        capture::{Direction, Endpoint, Frame},
+        transport::{MemoryTransport, Transport as _},
+    };
+
+    fn frame(timestamp: u64, endpoint: Endpoint, direction: Direction) -> Frame {
+        Frame {
+            timestamp,
+            endpoint,
+            direction,
+            data: vec![timestamp as u8],
+        }
+    }
+
+    #[test]
+    fn prefers_sent_frames() {
+        let frames = vec![
+            frame(2, Endpoint::Gui, Direction::Received),
+            frame(1, Endpoint::Tray, Direction::Sent),
+            frame(4, Endpoint::Gui, Direction::Sent),
+            frame(3, Endpoint::Tray, Direction::Received),
+        ];
+
+        assert_eq!(
+            frames_for(frames.clone(), Endpoint::Gui),
+            vec![frame(1, Endpoint::Tray, Direction::Sent)]
+        );
+        assert_eq!(
+            frames_for(frames, Endpoint::Tray),
+            vec![frame(4, Endpoint::Gui, Direction::Sent)]
+        );
+    }
+
+    #[test]
+    fn falls_back_to_received_frames() {
+        let frames = vec![
+            frame(5, Endpoint::Tray, Direction::Received),
+            frame(1, Endpoint::Tray, Direction::Sent),
+            frame(3, Endpoint::Tray, Direction::Received),
+        ];
+
+        assert_eq!(
+            frames_for(frames, Endpoint::Tray),
+            vec![
+                frame(3, Endpoint::Tray, Direction::Received),
+                frame(5, Endpoint::Tray, Direction::Received)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn replays_over_transport() {
+        let transport = MemoryTransport::new();
+        let frames = [GuiAction::Close, GuiAction::Quit]
+            .into_iter()
+            .enumerate()
+            .map(|(timestamp, action)| Frame {
+                timestamp: timestamp as u64,
+                endpoint: Endpoint::Tray,
+                direction: Direction::Sent,
+                data: bincode::encode_to_vec(action, BINCODE_CONF).expect("Can encode"),
+            })
+            .collect();
+
+        // Replaying into a GUI waits for it to connect, in place of the tray.
+        let replay = tokio::spawn(replay_over(transport.clone(), frames, Endpoint::Gui));
+        let gui = transport.connect().await.expect("Can connect");
+
+        let (mut rx, _tx) = MemoryTransport::split(gui);
+        assert_eq!(
+            rx.read_obj::<GuiAction>().await.expect("Can read"),
+            GuiAction::Close
+        );
+        assert_eq!(
+            rx.read_obj::<GuiAction>().await.expect("Can read"),
+            GuiAction::Quit
+        );
+        replay
+            .await
+            .expect("Replay finished")
+            .expect("Frames were sent");
+    }
+}
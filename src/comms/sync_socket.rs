@@ -3,7 +3,10 @@ use bincode::{
     error::{DecodeError, EncodeError},
 };
 
-use super::{BINCODE_CONF, BincodeConfiguration};
+use super::{
+    BINCODE_CONF, BincodeConfiguration,
+    capture::{self, Direction},
+};
 
 /// An error encountered when reading a data structure with [`ReadObj`].
 #[derive(thiserror::Error, Debug)]
@@ -36,6 +39,7 @@ impl<From: std::io::Read> ReadObj for From {
         let mut buf = vec![0; usize::from_ne_bytes(*buf)].into_boxed_slice();
         self.read_exact(&mut buf)?;
         log::trace!("Sync Read Data: {:?}", buf);
+        capture::record(Direction::Received, &buf);
 
         Ok(bincode::decode_from_slice_with_context(&buf, BINCODE_CONF, BINCODE_CONF)?.0)
 
@@ -69,6 +73,7 @@ impl<To: std::io::Write> WriteObj for To {
         let data = bincode::encode_to_vec(data, BINCODE_CONF)?;
         log::trace!("Sync Write Len: {}", data.len());
         log::trace!("Sync Write Data: {:?}", data);
+        capture::record(Direction::Sent, &data);
 
         self.write_all(&data.len().to_ne_bytes())?;
         self.write_all(data.as_slice())?;
@@ -0,0 +1,604 @@
+//! Keeps copies of the timers held by clients in sync with the tray.
+//!
+//! The tray sends a [`SyncMessage::Snapshot`] of every timer when a client connects, followed by sequence-numbered
+//! [`SyncMessage::Delta`]s as timers change. Running timers are ticked locally by each client, so nothing is sent
+//! while timers are left alone. A client that misses a delta asks the tray to resync, and receives a new snapshot.
+
+use std::{collections::BTreeMap, ops::Add, time::Duration};
+
+use bincode::{Decode, Encode};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    format::TimeFormat,
+    timer::{Appearance, KeepAwake, TimerData},
+};
+
+/// Identifies a timer across the tray & its clients.
+#[derive(
+    Decode, Encode, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug,
+)]
+pub struct TimerId(pub u64);
+
+/// A change to a single timer.
+#[derive(Decode, Encode, Clone, PartialEq, Debug)]
+pub enum Delta {
+    Created(TimerId, #[bincode(with_serde)] TimerData),
+    Updated(TimerId, #[bincode(with_serde)] TimerData),
+    Removed(TimerId),
+}
+
+/// A message sent from the tray to keep clients in sync.
+#[derive(Decode, Encode, Clone, PartialEq, Debug)]
+pub enum SyncMessage {
+    /// The state of every timer, as of the sequence number `seq`.
+    Snapshot {
+        seq: u64,
+        #[bincode(with_serde)]
+        timers: Vec<(TimerId, TimerData)>,
+    },
+    /// A change made after the message with the sequence number `seq - 1`.
+    Delta { seq: u64, delta: Delta },
+}
+
+/// A request from a client to change the timers held by the tray.
+#[derive(Decode, Encode, Clone, PartialEq, Debug)]
+pub enum TimerCommand {
+    /// Creates a new timer that ends after the given duration.
+    Create(Duration),
+    /// Pauses or resumes the timer.
+    Pause(TimerId, bool),
+    /// Sets the time that has passed for the timer to 0.
+    Reset(TimerId),
+    /// Extends the timer by the given amount of time.
+    AddTime(TimerId, Duration),
+    /// Shortens the timer by the given amount of time.
+    RemoveTime(TimerId, Duration),
+    /// Marks that the user has seen that the timer finished.
+    Acknowledge(TimerId),
+    /// Sets whether the timer is paused whilst the user is away from their computer.
+    PauseWhenAway(TimerId, bool),
+    /// Sets when the timer stops the computer from sleeping.
+    KeepAwake(TimerId, KeepAwake),
+    /// Sets how the timer is told apart from the others.
+    Appearance(TimerId, Appearance),
+    /// Sets how the timer's times are shown, or uses the format chosen for every timer if there is none.
+    TimeFormat(TimerId, Option<TimeFormat>),
+    /// Sets whether the timer keeps counting after it ends, until the user acknowledges it.
+    Overtime(TimerId, bool),
+    /// Forgets how long the timer has counted past its end.
+    ForgetOverrun(TimerId),
+    /// Removes the timer.
+    Remove(TimerId),
+}
+
+/// The authoritative state of every timer, held by the tray.
+///
+/// Each change produces the [`SyncMessage`] to be sent to clients.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct Timers {
+    timers: BTreeMap<TimerId, TimerData>,
+    /// The id given to the next timer that is created.
+    next_id: u64,
+    /// How long the timers that have been removed counted past their ends.
+    #[serde(default)]
+    removed_overrun: Duration,
+    /// The sequence number of the last message produced.
+    #[serde(skip)]
+    seq: u64,
+}
+
+impl Timers {
+    /// Creates a new timer.
+    pub fn create(&mut self, timer: TimerData) -> (TimerId, SyncMessage) {
+        let id = TimerId(self.next_id);
+        self.next_id += 1;
+
+        self.timers.insert(id, timer.clone());
+        (id, self.delta(Delta::Created(id, timer)))
+    }
+
+    /// Updates the timer with the given id, if it exists.
+    pub fn update(
+        &mut self,
+        id: TimerId,
+        update: impl FnOnce(&mut TimerData),
+    ) -> Option<SyncMessage> {
+        let timer = self.timers.get_mut(&id)?;
+        timer.tick();
+        update(timer);
+
+        let timer = timer.clone();
+        Some(self.delta(Delta::Updated(id, timer)))
+    }
+
+    /// Removes the timer with the given id, if it exists.
+    pub fn remove(&mut self, id: TimerId) -> Option<SyncMessage> {
+        let mut timer = self.timers.remove(&id)?;
+        timer.tick();
+        self.removed_overrun += timer.recorded_overrun();
+        Some(self.delta(Delta::Removed(id)))
+    }
+
+    /// Performs the given command, returning the message to send to clients if anything changed.
+    pub fn apply(&mut self, command: TimerCommand) -> Option<SyncMessage> {
+        match command {
+            TimerCommand::Create(end_after) => Some(self.create(TimerData::new(end_after)).1),
+            TimerCommand::Pause(id, pause) => self.update(id, |timer| timer.pause(pause)),
+            TimerCommand::Reset(id) => self.update(id, TimerData::reset),
+            TimerCommand::AddTime(id, extra) => self.update(id, |timer| timer.add_time(extra)),
+            TimerCommand::RemoveTime(id, less) => self.update(id, |timer| timer.remove_time(less)),
+            TimerCommand::Acknowledge(id) => self.update(id, TimerData::acknowledge),
+            TimerCommand::PauseWhenAway(id, pause) => {
+                self.update(id, |timer| timer.set_pause_when_away(pause))
+            }
+            TimerCommand::KeepAwake(id, keep_awake) => {
+                self.update(id, |timer| timer.set_keep_awake(keep_awake))
+            }
+            TimerCommand::Appearance(id, appearance) => {
+                self.update(id, |timer| timer.set_appearance(appearance))
+            }
+            TimerCommand::TimeFormat(id, time_format) => {
+                self.update(id, |timer| timer.set_time_format(time_format))
+            }
+            TimerCommand::Overtime(id, overtime) => {
+                self.update(id, |timer| timer.set_overtime(overtime))
+            }
+            TimerCommand::ForgetOverrun(id) => self.update(id, TimerData::forget_overrun),
+            TimerCommand::Remove(id) => self.remove(id),
+        }
+    }
+
+    /// The current state of every timer.
+    pub fn snapshot(&mut self) -> SyncMessage {
+        self.tick();
+        SyncMessage::Snapshot {
+            seq: self.seq,
+            timers: self
+                .timers
+                .iter()
+                .map(|(id, timer)| (*id, timer.clone()))
+                .collect(),
+        }
+    }
+
+    /// Increments the time that has passed for every timer.
+    pub fn tick(&mut self) {
+        self.timers.values_mut().for_each(TimerData::tick);
+    }
+
+    /// How long every timer has counted past its end in total, including those that have been removed.
+    pub fn recorded_overrun(&self) -> Duration {
+        self.timers
+            .values()
+            .map(TimerData::recorded_overrun)
+            .fold(self.removed_overrun, Duration::add)
+    }
+
+    /// How long the timers that have been removed counted past their ends.
+    pub fn removed_overrun(&self) -> Duration {
+        self.removed_overrun
+    }
+
+    /// Forgets how long every timer has counted past its end, including those that have been removed.
+    pub fn forget_overrun(&mut self) -> Vec<SyncMessage> {
+        self.removed_overrun = Duration::ZERO;
+
+        let ids: Vec<_> = self.timers.keys().copied().collect();
+        ids.into_iter()
+            .filter_map(|id| self.update(id, TimerData::forget_overrun))
+            .collect()
+    }
+
+    /// Every timer, in the order they were created.
+    pub fn iter(&self) -> impl Iterator<Item = (TimerId, &TimerData)> {
+        self.timers.iter().map(|(id, timer)| (*id, timer))
+    }
+
+    fn delta(&mut self, delta: Delta) -> SyncMessage {
+        self.seq += 1;
+        SyncMessage::Delta {
+            seq: self.seq,
+            delta,
+        }
+    }
+}
+
+/// A gap in the [`SyncMessage`]s received by a [`Replica`].
+#[derive(thiserror::Error, PartialEq, Debug)]
+#[error("Expected message {expected} but received {received}.")]
+pub struct Gap {
+    pub expected: u64,
+    pub received: u64,
+}
+
+/// A copy of the timers held by the tray, kept in sync by applying [`SyncMessage`]s.
+#[derive(Default)]
+pub struct Replica {
+    timers: BTreeMap<TimerId, TimerData>,
+    /// The sequence number of the last message applied.
+    /// If this is None, then a snapshot has not been received yet.
+    seq: Option<u64>,
+}
+
+impl Replica {
+    /// Applies a message from the tray.
+    ///
+    /// If a message was missed, a [`Gap`] is returned and deltas are ignored until the next snapshot is applied.
+    pub fn apply(&mut self, message: SyncMessage) -> Result<(), Gap> {
+        match message {
+            SyncMessage::Snapshot { seq, timers } => {
+                self.timers = timers.into_iter().collect();
+                self.seq = Some(seq);
+            }
+            SyncMessage::Delta { seq, delta } => {
+                let Some(last) = self.seq else {
+                    return Ok(());
+                };
+
+                // Deltas already included in a snapshot.
+                if seq <= last {
+                    return Ok(());
+                }
+
+                if seq != last + 1 {
+                    self.seq = None;
+                    return Err(Gap {
+                        expected: last + 1,
+                        received: seq,
+                    });
+                }
+
+                match delta {
+                    Delta::Created(id, timer) | Delta::Updated(id, timer) => {
+                        self.timers.insert(id, timer);
+                    }
+                    Delta::Removed(id) => {
+                        self.timers.remove(&id);
+                    }
+                }
+                self.seq = Some(seq);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether a snapshot has been applied since the last gap.
+    pub fn is_synced(&self) -> bool {
+        self.seq.is_some()
+    }
+
+    /// Every timer, in the order they were created.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (TimerId, &mut TimerData)> {
+        self.timers.iter_mut().map(|(id, timer)| (*id, timer))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use super::{Gap, Replica, SyncMessage, TimerCommand, TimerId, Timers};
+    use crate::{
+        comms::sync_socket::{ReadObj as _, WriteObj as _},
+        timer::{Appearance, Colour, KeepAwake, TimerData},
+    };
+
+    #[test]
+    fn replica_follows_deltas() {
+        let mut timers = Timers::default();
+        let mut replica = Replica::default();
+
+        replica.apply(timers.snapshot()).expect("Snapshot applies");
+
+        let create = timers
+            .apply(TimerCommand::Create(Duration::from_secs(60)))
+            .expect("Timer is created");
+        replica.apply(create).expect("In sequence");
+
+        let pause = timers
+            .apply(TimerCommand::Pause(TimerId(0), true))
+            .expect("Timer exists");
+        replica.apply(pause).expect("In sequence");
+
+        let (id, timer) = replica.iter_mut().next().expect("Timer was created");
+        assert_eq!(id, TimerId(0));
+        assert!(timer.paused());
+
+        let remove = timers
+            .apply(TimerCommand::Remove(TimerId(0)))
+            .expect("Timer exists");
+        replica.apply(remove).expect("In sequence");
+        assert_eq!(replica.iter_mut().count(), 0);
+
+        assert_eq!(timers.apply(TimerCommand::Remove(TimerId(0))), None);
+    }
+
+    #[test]
+    fn replica_detects_gaps() {
+        let mut timers = Timers::default();
+        let mut replica = Replica::default();
+
+        replica.apply(timers.snapshot()).expect("Snapshot applies");
+
+        let _missed = timers.apply(TimerCommand::Create(Duration::from_secs(1)));
+        let create = timers
+            .apply(TimerCommand::Create(Duration::from_secs(2)))
+            .expect("Timer is created");
+
+        assert_eq!(
+            replica.apply(create),
+            Err(Gap {
+                expected: 1,
+                received: 2
+            })
+        );
+        assert!(!replica.is_synced());
+
+        // Deltas are ignored until resynced.
+        let reset = timers.apply(TimerCommand::Reset(TimerId(1)));
+        replica
+            .apply(reset.expect("Timer exists"))
+            .expect("Ignored");
+        assert_eq!(replica.iter_mut().count(), 0);
+
+        replica.apply(timers.snapshot()).expect("Snapshot applies");
+        assert!(replica.is_synced());
+        assert_eq!(replica.iter_mut().count(), 2);
+    }
+
+    #[test]
+    fn replica_ignores_stale_deltas() {
+        let mut timers = Timers::default();
+        let mut replica = Replica::default();
+
+        let create = timers
+            .apply(TimerCommand::Create(Duration::from_secs(1)))
+            .expect("Timer is created");
+
+        replica.apply(timers.snapshot()).expect("Snapshot applies");
+        replica.apply(create).expect("Already in snapshot");
+        assert_eq!(replica.iter_mut().count(), 1);
+    }
+
+    #[test]
+    fn acknowledging_finished_timers() {
+        let mut timers = Timers::default();
+        let _ = timers.apply(TimerCommand::Create(Duration::ZERO));
+
+        let attention = |timers: &Timers| timers.iter().any(|(_, timer)| timer.needs_attention());
+        assert!(attention(&timers));
+
+        let _ = timers.apply(TimerCommand::Acknowledge(TimerId(0)));
+        assert!(!attention(&timers));
+
+        // Restarting the timer means it needs acknowledging again when it next finishes.
+        let _ = timers.apply(TimerCommand::Reset(TimerId(0)));
+        assert!(attention(&timers));
+    }
+
+    /// A timer with overtime on that ended the given time ago.
+    ///
+    /// A paused timer has already counted the overrun, whilst a running one counts it when next ticked, as if it was
+    /// saved when it ended.
+    fn overrunning(overrun: Duration, paused: bool) -> TimerData {
+        let (duration, ticked_at) = match paused {
+            true => (60 + overrun.as_secs(), "None".into()),
+            false => {
+                let ended = (SystemTime::now() - overrun)
+                    .duration_since(UNIX_EPOCH)
+                    .expect("Clock is after 1970");
+                let ended = format!(
+                    "Some((secs_since_epoch: {}, nanos_since_epoch: {}))",
+                    ended.as_secs(),
+                    ended.subsec_nanos()
+                );
+                (60, ended)
+            }
+        };
+        ron::from_str(&format!(
+            "(duration: (secs: {duration}, nanos: 0), end_after: (secs: 60, nanos: 0), paused: {paused}, \
+            overtime: true, ticked_at: {ticked_at})"
+        ))
+        .expect("Timer is valid")
+    }
+
+    #[test]
+    fn overtime() {
+        let mut timers = Timers::default();
+        let (id, _) = timers.create(overrunning(Duration::from_secs(90), false));
+
+        // The timer keeps counting past its end.
+        let overrun = |timers: &Timers| {
+            let (_, timer) = timers.iter().next().expect("Timer was created");
+            timer.overrun()
+        };
+        timers.tick();
+        assert!(overrun(&timers) >= Duration::from_secs(90));
+        let (_, timer) = timers.iter().next().expect("Timer was created");
+        assert!(timer.finished());
+        assert_eq!(timer.progress(), 1.0);
+
+        // Acknowledging the timer stops the count, keeping the overrun.
+        let _ = timers.apply(TimerCommand::Acknowledge(id));
+        let acknowledged = overrun(&timers);
+        timers.tick();
+        assert_eq!(overrun(&timers), acknowledged);
+
+        // Turning overtime off stops showing the overrun, though it is still recorded.
+        let _ = timers.apply(TimerCommand::Overtime(id, false));
+        assert_eq!(overrun(&timers), Duration::ZERO);
+        assert_eq!(timers.recorded_overrun(), acknowledged);
+    }
+
+    #[test]
+    fn recording_overrun() {
+        let mut timers = Timers::default();
+        let secs = Duration::from_secs;
+        let [restarted, extended, removed] =
+            [90, 30, 20].map(|overrun| timers.create(overrunning(secs(overrun), true)).0);
+        let _ = timers.apply(TimerCommand::Create(secs(60)));
+        assert_eq!(timers.recorded_overrun(), secs(140));
+
+        // The overrun of each run is kept once the timer is restarted.
+        let _ = timers.apply(TimerCommand::Reset(restarted));
+        assert_eq!(timers.recorded_overrun(), secs(140));
+
+        // Extending the timer past its overrun keeps it too.
+        let _ = timers.apply(TimerCommand::AddTime(extended, secs(60)));
+        assert_eq!(timers.recorded_overrun(), secs(140));
+
+        // As does removing the timer.
+        let _ = timers.apply(TimerCommand::Remove(removed));
+        assert_eq!(timers.recorded_overrun(), secs(140));
+        let overruns: Vec<_> = timers
+            .iter()
+            .map(|(_, timer)| (timer.overrun(), timer.recorded_overrun()))
+            .collect();
+        assert_eq!(
+            overruns,
+            [(secs(0), secs(90)), (secs(0), secs(30)), (secs(0), secs(0))]
+        );
+    }
+
+    #[test]
+    fn forgetting_overrun() {
+        let mut timers = Timers::default();
+        let secs = Duration::from_secs;
+        let [forgotten, _, removed] =
+            [90, 30, 20].map(|overrun| timers.create(overrunning(secs(overrun), true)).0);
+        let _ = timers.apply(TimerCommand::Remove(removed));
+
+        // The timer still shows its overrun, though it is no longer recorded.
+        let _ = timers.apply(TimerCommand::ForgetOverrun(forgotten));
+        let (_, timer) = timers.iter().next().expect("Timer was created");
+        assert_eq!(timer.overrun(), secs(90));
+        assert_eq!(timers.recorded_overrun(), secs(50));
+        let _ = timers.apply(TimerCommand::AddTime(forgotten, secs(60)));
+        assert_eq!(timers.recorded_overrun(), secs(50));
+
+        // Every timer's overrun is forgotten at once, including the removed timers'.
+        assert_eq!(timers.forget_overrun().len(), 2);
+        assert_eq!(timers.recorded_overrun(), Duration::ZERO);
+    }
+
+    #[test]
+    fn removing_time() {
+        let mut timers = Timers::default();
+        let _ = timers.apply(TimerCommand::Create(Duration::from_secs(90)));
+
+        let _ = timers.apply(TimerCommand::RemoveTime(
+            TimerId(0),
+            Duration::from_secs(60),
+        ));
+        let (_, timer) = timers.iter().next().expect("Timer was created");
+        assert_eq!(timer.end_after(), Duration::from_secs(30));
+
+        // Removing more time than is left ends the timer.
+        let _ = timers.apply(TimerCommand::RemoveTime(
+            TimerId(0),
+            Duration::from_secs(60),
+        ));
+        let (_, timer) = timers.iter().next().expect("Timer was created");
+        assert!(timer.finished());
+        assert_eq!(timer.remaining(), Duration::ZERO);
+
+        // A timer counting overtime ends now, only recording the time it counted past its old end.
+        let overrunning = ron::from_str(
+            "(duration: (secs: 100, nanos: 0), end_after: (secs: 90, nanos: 0), paused: true, \
+            overtime: true)",
+        )
+        .expect("Timer is valid");
+        let (id, _) = timers.create(overrunning);
+        let _ = timers.apply(TimerCommand::RemoveTime(id, Duration::from_secs(60)));
+        let (_, timer) = timers.iter().nth(1).expect("Timer was created");
+        assert_eq!(timer.end_after(), Duration::from_secs(30));
+        assert_eq!(timer.overrun(), Duration::ZERO);
+        assert_eq!(timer.recorded_overrun(), Duration::from_secs(10));
+    }
+
+    #[test]
+    fn keeping_awake() {
+        let mut timers = Timers::default();
+        let _ = timers.apply(TimerCommand::Create(Duration::from_secs(10 * 60)));
+        let keeps_awake = |timers: &Timers| {
+            let (_, timer) = timers.iter().next().expect("Timer was created");
+            timer.keeps_awake()
+        };
+        assert!(!keeps_awake(&timers));
+
+        // Only the final minutes keep the computer awake.
+        let _ = timers.apply(TimerCommand::KeepAwake(
+            TimerId(0),
+            KeepAwake::Final(Duration::from_secs(5 * 60)),
+        ));
+        assert!(!keeps_awake(&timers));
+        let _ = timers.apply(TimerCommand::RemoveTime(
+            TimerId(0),
+            Duration::from_secs(6 * 60),
+        ));
+        assert!(keeps_awake(&timers));
+
+        // Paused & finished timers let the computer sleep.
+        let _ = timers.apply(TimerCommand::Pause(TimerId(0), true));
+        assert!(!keeps_awake(&timers));
+        let _ = timers.apply(TimerCommand::Pause(TimerId(0), false));
+        let _ = timers.apply(TimerCommand::KeepAwake(TimerId(0), KeepAwake::WhileRunning));
+        assert!(keeps_awake(&timers));
+        let _ = timers.apply(TimerCommand::RemoveTime(
+            TimerId(0),
+            Duration::from_secs(10 * 60),
+        ));
+        assert!(!keeps_awake(&timers));
+    }
+
+    #[test]
+    fn naming_timers() {
+        let mut timers = Timers::default();
+        let _ = timers.apply(TimerCommand::Create(Duration::from_secs(60)));
+        let name = |timers: &Timers| {
+            let (_, timer) = timers.iter().next().expect("Timer was created");
+            timer.appearance().name(|| "Timer 1".into())
+        };
+        assert_eq!(name(&timers), "Timer 1");
+
+        let appearance = Appearance {
+            label: " Tea ".into(),
+            colour: Colour::Green,
+            emoji: "🍵".into(),
+        };
+        let message = timers.apply(TimerCommand::Appearance(TimerId(0), appearance.clone()));
+        assert!(message.is_some());
+        assert_eq!(name(&timers), "🍵 Tea");
+
+        // Only an emoji keeps the usual name.
+        let _ = timers.apply(TimerCommand::Appearance(
+            TimerId(0),
+            Appearance {
+                label: String::new(),
+                ..appearance
+            },
+        ));
+        assert_eq!(name(&timers), "🍵 Timer 1");
+    }
+
+    #[test]
+    fn message_encoding() {
+        let mut timers = Timers::default();
+        let _ = timers.apply(TimerCommand::Create(Duration::from_secs(5)));
+
+        let mut buf = Vec::new();
+        buf.write_obj(timers.snapshot()).expect("Can write");
+
+        let SyncMessage::Snapshot { seq, timers } = buf.as_slice().read_obj().expect("Can read")
+        else {
+            panic!("Snapshot was not read back");
+        };
+
+        assert_eq!(seq, 1);
+        assert_eq!(timers.len(), 1);
+        assert_eq!(timers[0].0, TimerId(0));
+        assert_eq!(timers[0].1.end_after(), Duration::from_secs(5));
+    }
+}
@@ -0,0 +1,379 @@
+use std::{
+    future::Future, io::ErrorKind, net::SocketAddr, os::fd::AsFd, path::PathBuf, sync::Mutex,
+};
+
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::{TcpListener, TcpStream, UnixListener, UnixStream, tcp, unix},
+    sync::mpsc::{self, UnboundedReceiver, UnboundedSender},
+};
+
+/// A way for the GUI & the tray to communicate.
+///
+/// The tray [`listen`](Self::listen)s for GUIs, which [`connect`](Self::connect) to it.
+pub trait Transport: Send + Sync + 'static {
+    /// A connection between the tray & a GUI.
+    type Stream: AsyncRead + AsyncWrite + Send + Unpin + 'static;
+    /// The reading half of a [`Stream`](Self::Stream).
+    type ReadHalf: AsyncRead + Send + Unpin + 'static;
+    /// The writing half of a [`Stream`](Self::Stream).
+    type WriteHalf: AsyncWrite + Send + Unpin + 'static;
+    /// Accepts connections made with this transport.
+    type Listener: Listener<Stream = Self::Stream>;
+
+    /// Starts listening for connections.
+    fn listen(&self) -> impl Future<Output = std::io::Result<Self::Listener>> + Send;
+
+    /// Connects to a [`Listener`] of this transport.
+    fn connect(&self) -> impl Future<Output = std::io::Result<Self::Stream>> + Send;
+
+    /// Splits a connection into halves that can be used concurrently.
+    fn split(stream: Self::Stream) -> (Self::ReadHalf, Self::WriteHalf);
+}
+
+/// Accepts connections for a [`Transport`].
+pub trait Listener: Send + 'static {
+    /// A connection between the tray & a GUI.
+    type Stream;
+
+    /// Waits for the next connection.
+    fn accept(&mut self) -> impl Future<Output = std::io::Result<Self::Stream>> + Send;
+}
+
+/// Communication over a TCP socket.
+pub struct TcpTransport {
+    addr: SocketAddr,
+    /// A listener that is already bound, such as one passed in by systemd.
+    listener: Mutex<Option<std::net::TcpListener>>,
+}
+
+impl TcpTransport {
+    /// Creates a transport that communicates on the given address.
+    pub fn new(addr: SocketAddr) -> Self {
+        Self {
+            addr,
+            listener: Mutex::new(None),
+        }
+    }
+
+    /// Listens with the given listener instead of binding the address.
+    pub fn with_listener(self, listener: std::net::TcpListener) -> Self {
+        Self {
+            listener: Mutex::new(Some(listener)),
+            ..self
+        }
+    }
+}
+
+impl Transport for TcpTransport {
+    type Stream = TcpStream;
+    type ReadHalf = tcp::OwnedReadHalf;
+    type WriteHalf = tcp::OwnedWriteHalf;
+    type Listener = TcpListener;
+
+    async fn listen(&self) -> std::io::Result<Self::Listener> {
+        if let Some(listener) = take(&self.listener) {
+            listener.set_nonblocking(true)?;
+            return TcpListener::from_std(listener);
+        }
+
+        TcpListener::bind(self.addr).await
+    }
+
+    async fn connect(&self) -> std::io::Result<Self::Stream> {
+        TcpStream::connect(self.addr).await
+    }
+
+    fn split(stream: Self::Stream) -> (Self::ReadHalf, Self::WriteHalf) {
+        stream.into_split()
+    }
+}
+
+impl Listener for TcpListener {
+    type Stream = TcpStream;
+
+    async fn accept(&mut self) -> std::io::Result<Self::Stream> {
+        Ok(TcpListener::accept(self).await?.0)
+    }
+}
+
+/// Communication over a unix domain socket.
+pub struct UnixTransport {
+    path: PathBuf,
+    /// A listener that is already bound, such as one passed in by systemd.
+    listener: Mutex<Option<std::os::unix::net::UnixListener>>,
+}
+
+impl UnixTransport {
+    /// Creates a transport that communicates on the socket at the given path.
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            listener: Mutex::new(None),
+        }
+    }
+
+    /// Listens with the given listener instead of binding the path.
+    pub fn with_listener(self, listener: std::os::unix::net::UnixListener) -> Self {
+        Self {
+            listener: Mutex::new(Some(listener)),
+            ..self
+        }
+    }
+}
+
+impl Transport for UnixTransport {
+    type Stream = UnixStream;
+    type ReadHalf = unix::OwnedReadHalf;
+    type WriteHalf = unix::OwnedWriteHalf;
+    type Listener = UnixSocket;
+
+    async fn listen(&self) -> std::io::Result<Self::Listener> {
+        if let Some(listener) = take(&self.listener) {
+            listener.set_nonblocking(true)?;
+            // Whoever bound the socket is responsible for removing it.
+            return Ok(UnixSocket {
+                listener: UnixListener::from_std(listener)?,
+                path: None,
+            });
+        }
+
+        if UnixStream::connect(&self.path).await.is_ok() {
+            return Err(std::io::Error::new(
+                ErrorKind::AddrInUse,
+                "Another tray is listening on this socket",
+            ));
+        }
+
+        // Remove the socket left behind by a previous tray.
+        match std::fs::remove_file(&self.path) {
+            Err(err) if err.kind() != ErrorKind::NotFound => return Err(err),
+            _ => {}
+        }
+
+        Ok(UnixSocket {
+            listener: UnixListener::bind(&self.path)?,
+            path: Some(self.path.clone()),
+        })
+    }
+
+    async fn connect(&self) -> std::io::Result<Self::Stream> {
+        UnixStream::connect(&self.path).await
+    }
+
+    fn split(stream: Self::Stream) -> (Self::ReadHalf, Self::WriteHalf) {
+        stream.into_split()
+    }
+}
+
+/// A listener on a unix domain socket, which removes the socket it bound when dropped.
+pub struct UnixSocket {
+    listener: UnixListener,
+    /// The socket bound by the listener.
+    path: Option<PathBuf>,
+}
+
+impl Listener for UnixSocket {
+    type Stream = UnixStream;
+
+    async fn accept(&mut self) -> std::io::Result<Self::Stream> {
+        Ok(self.listener.accept().await?.0)
+    }
+}
+
+impl Drop for UnixSocket {
+    fn drop(&mut self) {
+        if let Some(path) = &self.path
+            && let Err(err) = std::fs::remove_file(path)
+        {
+            log::warn!("Unable to remove socket '{}': {err}", path.display());
+        }
+    }
+}
+
+/// Takes the value out of the mutex, if it has not been taken already.
+fn take<T>(value: &Mutex<Option<T>>) -> Option<T> {
+    value.lock().ok().and_then(|mut value| value.take())
+}
+
+/// Communication over socket pairs created by the tray when it spawns a GUI.
+///
+/// The GUI receives its end of the socket pair as its stdin.
+/// A [`Default`] pipe transport can only [`connect`](Transport::connect), as is done by the GUI.
+#[derive(Default)]
+pub struct PipeTransport {
+    pipes: Mutex<Option<UnboundedReceiver<std::os::unix::net::UnixStream>>>,
+}
+
+impl PipeTransport {
+    /// Creates a transport that accepts the socket pairs passed into the returned sender.
+    pub fn new() -> (Self, UnboundedSender<std::os::unix::net::UnixStream>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let transport = Self {
+            pipes: Mutex::new(Some(receiver)),
+        };
+        (transport, sender)
+    }
+}
+
+impl Transport for PipeTransport {
+    type Stream = UnixStream;
+    type ReadHalf = unix::OwnedReadHalf;
+    type WriteHalf = unix::OwnedWriteHalf;
+    type Listener = UnboundedReceiver<std::os::unix::net::UnixStream>;
+
+    async fn listen(&self) -> std::io::Result<Self::Listener> {
+        take(&self.pipes)
+            .ok_or_else(|| std::io::Error::new(ErrorKind::AddrInUse, "Already listening"))
+    }
+
+    async fn connect(&self) -> std::io::Result<Self::Stream> {
+        let fd = std::io::stdin().as_fd().try_clone_to_owned()?;
+        let stream = std::os::unix::net::UnixStream::from(fd);
+        stream.set_nonblocking(true)?;
+        UnixStream::from_std(stream)
+    }
+
+    fn split(stream: Self::Stream) -> (Self::ReadHalf, Self::WriteHalf) {
+        stream.into_split()
+    }
+}
+
+impl Listener for UnboundedReceiver<std::os::unix::net::UnixStream> {
+    type Stream = UnixStream;
+
+    async fn accept(&mut self) -> std::io::Result<Self::Stream> {
+        let stream = self.recv().await.ok_or_else(|| {
+            std::io::Error::new(ErrorKind::BrokenPipe, "No more GUIs can be spawned")
+        })?;
+        stream.set_nonblocking(true)?;
+        UnixStream::from_std(stream)
+    }
+}
+
+#[cfg(test)]
+use std::sync::Arc;
+#[cfg(test)]
+use tokio::io::{DuplexStream, ReadHalf, WriteHalf};
+
+/// In-memory communication, for connecting the tray & GUI within the same process.
+#[cfg(test)]
+#[derive(Clone)]
+pub struct MemoryTransport {
+    sender: UnboundedSender<DuplexStream>,
+    receiver: Arc<Mutex<Option<UnboundedReceiver<DuplexStream>>>>,
+}
+
+#[cfg(test)]
+impl MemoryTransport {
+    /// The size of the buffer in each direction of a connection.
+    const BUFFER_SIZE: usize = 4096;
+
+    pub fn new() -> Self {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        Self {
+            sender,
+            receiver: Arc::new(Mutex::new(Some(receiver))),
+        }
+    }
+}
+
+#[cfg(test)]
+impl Transport for MemoryTransport {
+    type Stream = DuplexStream;
+    type ReadHalf = ReadHalf<DuplexStream>;
+    type WriteHalf = WriteHalf<DuplexStream>;
+    type Listener = UnboundedReceiver<DuplexStream>;
+
+    async fn listen(&self) -> std::io::Result<Self::Listener> {
+        take(&self.receiver)
+            .ok_or_else(|| std::io::Error::new(ErrorKind::AddrInUse, "Already listening"))
+    }
+
+    async fn connect(&self) -> std::io::Result<Self::Stream> {
+        let (client, server) = tokio::io::duplex(Self::BUFFER_SIZE);
+        self.sender
+            .send(server)
+            .map_err(|_| std::io::Error::new(ErrorKind::ConnectionRefused, "Not listening"))?;
+        Ok(client)
+    }
+
+    fn split(stream: Self::Stream) -> (Self::ReadHalf, Self::WriteHalf) {
+        tokio::io::split(stream)
+    }
+}
+
+#[cfg(test)]
+impl Listener for UnboundedReceiver<DuplexStream> {
+    type Stream = DuplexStream;
+
+    async fn accept(&mut self) -> std::io::Result<Self::Stream> {
+        self.recv().await.ok_or_else(|| {
+            std::io::Error::new(ErrorKind::BrokenPipe, "All connectors have been dropped")
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Listener, MemoryTransport, Transport, UnixTransport};
+    use crate::comms::{
+        GuiAction, GuiResponse,
+        async_socket::{AsyncReadObj as _, AsyncWriteObj as _},
+    };
+
+    #[tokio::test]
+    async fn memory_round_trip() {
+        let transport = MemoryTransport::new();
+        let mut listener = transport.listen().await.expect("Can listen");
+        assert!(transport.listen().await.is_err());
+
+        let client = transport.connect().await.expect("Can connect");
+        let server = listener.accept().await.expect("Can accept");
+
+        let (mut client_rx, mut client_tx) = MemoryTransport::split(client);
+        let (mut server_rx, mut server_tx) = MemoryTransport::split(server);
+
+        client_tx
+            .write_obj(GuiResponse::Opened)
+            .await
+            .expect("Can write");
+        assert_eq!(
+            server_rx.read_obj::<GuiResponse>().await.expect("Can read"),
+            GuiResponse::Opened
+        );
+
+        server_tx
+            .write_obj(GuiAction::Close)
+            .await
+            .expect("Can write");
+        assert_eq!(
+            client_rx.read_obj::<GuiAction>().await.expect("Can read"),
+            GuiAction::Close
+        );
+    }
+
+    #[tokio::test]
+    async fn unix_round_trip() {
+        let dir = tempfile::tempdir().expect("Can create temp dir");
+        let transport = UnixTransport::new(dir.path().join("socket"));
+        let mut listener = transport.listen().await.expect("Can listen");
+
+        let (client, server) = tokio::join!(transport.connect(), Listener::accept(&mut listener));
+        let (_, mut client_tx) = UnixTransport::split(client.expect("Can connect"));
+        let (mut server_rx, _) = UnixTransport::split(server.expect("Can accept"));
+
+        client_tx
+            .write_obj(GuiResponse::Closed)
+            .await
+            .expect("Can write");
+        assert_eq!(
+            server_rx.read_obj::<GuiResponse>().await.expect("Can read"),
+            GuiResponse::Closed
+        );
+
+        // The socket is removed once the tray stops listening.
+        drop(listener);
+        assert!(!dir.path().join("socket").exists());
+    }
+}
@@ -0,0 +1,133 @@
+//! Do not disturb, during which the user is not alerted when timers finish.
+
+use std::time::SystemTime;
+
+use crate::clock::LocalTime;
+
+/// When the user does not want to be alerted that timers have finished.
+#[derive(
+    bincode::Decode,
+    bincode::Encode,
+    serde::Serialize,
+    serde::Deserialize,
+    Clone,
+    PartialEq,
+    Debug,
+    Default,
+)]
+#[serde(default)]
+pub struct DoNotDisturb {
+    /// Whether the user turned on do not disturb.
+    pub enabled: bool,
+    /// A daily period during which do not disturb is on.
+    pub quiet_hours: Option<QuietHours>,
+}
+
+impl DoNotDisturb {
+    /// Whether do not disturb is on at the given time of day.
+    pub fn active_at(&self, time: LocalTime) -> bool {
+        self.enabled
+            || self
+                .quiet_hours
+                .is_some_and(|quiet_hours| quiet_hours.contains(time))
+    }
+
+    /// Whether do not disturb is on now.
+    pub fn active(&self) -> bool {
+        self.active_at(LocalTime::at(SystemTime::now()))
+    }
+}
+
+/// A daily period of time, which may continue past midnight.
+#[derive(
+    bincode::Decode,
+    bincode::Encode,
+    serde::Serialize,
+    serde::Deserialize,
+    Clone,
+    Copy,
+    PartialEq,
+    Debug,
+)]
+pub struct QuietHours {
+    /// When the period starts, inclusive.
+    pub start: LocalTime,
+    /// When the period ends, exclusive.
+    pub end: LocalTime,
+}
+
+impl Default for QuietHours {
+    fn default() -> Self {
+        Self {
+            start: LocalTime {
+                hour: 22,
+                minute: 0,
+            },
+            end: LocalTime { hour: 7, minute: 0 },
+        }
+    }
+}
+
+impl QuietHours {
+    /// Whether the time of day is within the period.
+    pub fn contains(&self, time: LocalTime) -> bool {
+        match self.start <= self.end {
+            true => self.start <= time && time < self.end,
+            // The period continues past midnight.
+            false => self.start <= time || time < self.end,
+        }
+    }
+}
+
+impl std::fmt::Display for QuietHours {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}–{}", self.start, self.end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DoNotDisturb, QuietHours};
+    use crate::clock::LocalTime;
+
+    fn time(hour: u8, minute: u8) -> LocalTime {
+        LocalTime { hour, minute }
+    }
+
+    #[test]
+    fn quiet_hours_within_day() {
+        let quiet_hours = QuietHours {
+            start: time(12, 30),
+            end: time(13, 30),
+        };
+
+        assert!(!quiet_hours.contains(time(12, 29)));
+        assert!(quiet_hours.contains(time(12, 30)));
+        assert!(quiet_hours.contains(time(13, 29)));
+        assert!(!quiet_hours.contains(time(13, 30)));
+    }
+
+    #[test]
+    fn quiet_hours_past_midnight() {
+        let quiet_hours = QuietHours::default();
+
+        assert!(!quiet_hours.contains(time(21, 59)));
+        assert!(quiet_hours.contains(time(22, 0)));
+        assert!(quiet_hours.contains(time(0, 0)));
+        assert!(quiet_hours.contains(time(6, 59)));
+        assert!(!quiet_hours.contains(time(7, 0)));
+    }
+
+    #[test]
+    fn active() {
+        let mut dnd = DoNotDisturb::default();
+        assert!(!dnd.active_at(time(23, 0)));
+
+        dnd.quiet_hours = Some(QuietHours::default());
+        assert!(dnd.active_at(time(23, 0)));
+        assert!(!dnd.active_at(time(12, 0)));
+
+        dnd.enabled = true;
+        assert!(dnd.active_at(time(12, 0)));
+    }
+}
@@ -0,0 +1,319 @@
+//! Formats how long timers are & how much time they have left, in the style the user chose.
+
+use std::time::{Duration, SystemTime};
+
+use crate::{clock::LocalTime, timer::TimerData};
+
+const MINUTE: u64 = 60;
+const HOUR: u64 = 60 * MINUTE;
+const DAY: u64 = 24 * HOUR;
+
+/// How times are shown to the user.
+#[derive(
+    bincode::Decode,
+    bincode::Encode,
+    serde::Serialize,
+    serde::Deserialize,
+    Clone,
+    Copy,
+    PartialEq,
+    Debug,
+    Default,
+)]
+pub enum TimeFormat {
+    /// Hours, minutes & seconds, such as `01:05:00`.
+    #[default]
+    Clock,
+    /// Only the largest units, such as `1h 5m`.
+    Compact,
+    /// Minutes & seconds to a tenth of a second, such as `04:59.9`.
+    Tenths,
+    /// How much of the timer has passed, such as `42%`.
+    Percent,
+    /// Days, then hours, minutes & seconds, such as `2d 03:00:00`.
+    Days,
+    /// The time of day the timer ends, such as `ends at 14:32`.
+    EndsAt,
+}
+
+impl TimeFormat {
+    /// Every format, in the order they are offered to the user.
+    pub const ALL: [TimeFormat; 6] = [
+        TimeFormat::Clock,
+        TimeFormat::Compact,
+        TimeFormat::Tenths,
+        TimeFormat::Percent,
+        TimeFormat::Days,
+        TimeFormat::EndsAt,
+    ];
+
+    /// The name of the format, as shown to the user.
+    pub fn name(self) -> &'static str {
+        match self {
+            TimeFormat::Clock => "Clock",
+            TimeFormat::Compact => "Compact",
+            TimeFormat::Tenths => "Tenths of a second",
+            TimeFormat::Percent => "Percent complete",
+            TimeFormat::Days => "Days",
+            TimeFormat::EndsAt => "Ends at",
+        }
+    }
+
+    /// The time left on the timer.
+    pub fn remaining(self, timer: &TimerData) -> String {
+        self.remaining_at(timer, SystemTime::now())
+    }
+
+    /// The time left on the timer, if it is now the given time.
+    fn remaining_at(self, timer: &TimerData, now: SystemTime) -> String {
+        let overrun = timer.overrun();
+        if !overrun.is_zero() {
+            return format!("+{}", self.overrun(overrun));
+        }
+
+        let remaining = timer.remaining();
+        match self {
+            TimeFormat::Clock => clock(remaining),
+            TimeFormat::Compact => compact(remaining),
+            TimeFormat::Tenths => tenths(remaining),
+            TimeFormat::Percent => percent(timer.progress()),
+            TimeFormat::Days => days(remaining),
+            // A paused timer has no end yet.
+            TimeFormat::EndsAt if timer.finished() => "ended".into(),
+            TimeFormat::EndsAt if timer.paused() => format!("{} left", clock(remaining)),
+            TimeFormat::EndsAt => ends_at(LocalTime::at(now + remaining)),
+        }
+    }
+
+    /// How long a timer has counted past its end.
+    pub fn overrun(self, overrun: Duration) -> String {
+        match self {
+            TimeFormat::Compact => compact(overrun),
+            TimeFormat::Tenths => tenths(overrun),
+            TimeFormat::Days => days(overrun),
+            TimeFormat::Clock | TimeFormat::Percent | TimeFormat::EndsAt => clock(overrun),
+        }
+    }
+
+    /// How long a timer lasts for.
+    pub fn total(self, end_after: Duration) -> String {
+        match self {
+            TimeFormat::Compact => compact(end_after),
+            TimeFormat::Days => days(end_after),
+            TimeFormat::Clock | TimeFormat::Tenths | TimeFormat::Percent | TimeFormat::EndsAt => {
+                clock(end_after)
+            }
+        }
+    }
+}
+
+/// Whole seconds, rounded up so a timer only shows no time left once it has finished.
+fn whole_secs(time: Duration) -> u64 {
+    let secs = time.as_secs();
+    match time.subsec_nanos() {
+        0 => secs,
+        _ => secs + 1,
+    }
+}
+
+/// Hours, minutes & seconds, as `HH:MM:SS`.
+fn clock(time: Duration) -> String {
+    let secs = whole_secs(time);
+    let (hours, minutes, secs) = (secs / HOUR, secs / MINUTE % 60, secs % MINUTE);
+    format!("{hours:0>2}:{minutes:0>2}:{secs:0>2}")
+}
+
+/// The two largest units of the time, leaving out any that are zero.
+fn compact(time: Duration) -> String {
+    let secs = whole_secs(time);
+    let units = [
+        (secs / DAY, "d"),
+        (secs / HOUR % 24, "h"),
+        (secs / MINUTE % 60, "m"),
+        (secs % MINUTE, "s"),
+    ];
+
+    let Some(largest) = units.iter().position(|(amount, _)| *amount != 0) else {
+        return "0s".into();
+    };
+
+    units[largest..]
+        .iter()
+        .take(2)
+        .filter(|(amount, _)| *amount != 0)
+        .map(|(amount, unit)| format!("{amount}{unit}"))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Minutes & seconds to a tenth of a second, with hours when there are any.
+fn tenths(time: Duration) -> String {
+    // Rounded up, for the same reason as whole seconds.
+    let tenths = time.as_millis().div_ceil(100) as u64;
+    let secs = tenths / 10;
+    let (hours, minutes, secs, tenths) =
+        (secs / HOUR, secs / MINUTE % 60, secs % MINUTE, tenths % 10);
+
+    match hours {
+        0 => format!("{minutes:0>2}:{secs:0>2}.{tenths}"),
+        _ => format!("{hours}:{minutes:0>2}:{secs:0>2}.{tenths}"),
+    }
+}
+
+/// The fraction of the timer that has passed, as a whole percentage.
+fn percent(progress: f32) -> String {
+    // Rounded down, so a timer is only complete once it has finished.
+    let percent = (progress.clamp(0.0, 1.0) * 100.0).floor();
+    format!("{percent:.0}%")
+}
+
+/// Days, then hours, minutes & seconds, leaving out the days when there are none.
+fn days(time: Duration) -> String {
+    let secs = whole_secs(time);
+    let (days, hours, minutes, secs) = (
+        secs / DAY,
+        secs / HOUR % 24,
+        secs / MINUTE % 60,
+        secs % MINUTE,
+    );
+
+    match days {
+        0 => format!("{hours:0>2}:{minutes:0>2}:{secs:0>2}"),
+        _ => format!("{days}d {hours:0>2}:{minutes:0>2}:{secs:0>2}"),
+    }
+}
+
+/// The time of day the timer ends.
+fn ends_at(end: LocalTime) -> String {
+    format!("ends at {end}")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use super::{TimeFormat, clock, compact, days, ends_at, percent, tenths};
+    use crate::{clock::LocalTime, timer::TimerData};
+
+    fn secs(secs: u64) -> Duration {
+        Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn clock_style() {
+        assert_eq!(clock(Duration::ZERO), "00:00:00");
+        assert_eq!(clock(secs(59)), "00:00:59");
+        // Seconds are within the minute.
+        assert_eq!(clock(secs(90)), "00:01:30");
+        assert_eq!(clock(secs(3599)), "00:59:59");
+        assert_eq!(clock(secs(3600)), "01:00:00");
+        assert_eq!(clock(secs(3600 + 5 * 60 + 7)), "01:05:07");
+        assert_eq!(clock(secs(25 * 3600)), "25:00:00");
+    }
+
+    #[test]
+    fn partial_seconds_round_up() {
+        assert_eq!(clock(Duration::from_millis(1)), "00:00:01");
+        assert_eq!(clock(Duration::from_millis(59_001)), "00:01:00");
+        assert_eq!(compact(Duration::from_millis(500)), "1s");
+        assert_eq!(days(Duration::from_millis(86_399_500)), "1d 00:00:00");
+    }
+
+    #[test]
+    fn compact_style() {
+        assert_eq!(compact(Duration::ZERO), "0s");
+        assert_eq!(compact(secs(45)), "45s");
+        assert_eq!(compact(secs(5 * 60 + 3)), "5m 3s");
+        assert_eq!(compact(secs(5 * 60)), "5m");
+        assert_eq!(compact(secs(3600 + 5 * 60 + 30)), "1h 5m");
+        assert_eq!(compact(secs(3600 + 30)), "1h");
+        assert_eq!(compact(secs(2 * 86_400 + 3 * 3600 + 59)), "2d 3h");
+    }
+
+    #[test]
+    fn tenths_style() {
+        assert_eq!(tenths(Duration::ZERO), "00:00.0");
+        assert_eq!(tenths(Duration::from_millis(299_900)), "04:59.9");
+        assert_eq!(tenths(Duration::from_millis(299_901)), "05:00.0");
+        assert_eq!(tenths(Duration::from_millis(90_050)), "01:30.1");
+        assert_eq!(tenths(Duration::from_millis(3_600_000)), "1:00:00.0");
+    }
+
+    #[test]
+    fn percent_style() {
+        assert_eq!(percent(0.0), "0%");
+        assert_eq!(percent(0.425), "42%");
+        assert_eq!(percent(0.999), "99%");
+        assert_eq!(percent(1.0), "100%");
+        assert_eq!(percent(1.5), "100%");
+    }
+
+    #[test]
+    fn days_style() {
+        assert_eq!(days(secs(90)), "00:01:30");
+        assert_eq!(days(secs(86_400)), "1d 00:00:00");
+        assert_eq!(
+            days(secs(2 * 86_400 + 3 * 3600 + 4 * 60 + 5)),
+            "2d 03:04:05"
+        );
+    }
+
+    #[test]
+    fn ends_at_style() {
+        assert_eq!(
+            ends_at(LocalTime {
+                hour: 14,
+                minute: 32
+            }),
+            "ends at 14:32"
+        );
+        assert_eq!(ends_at(LocalTime { hour: 9, minute: 5 }), "ends at 09:05");
+    }
+
+    #[test]
+    fn timers() {
+        let mut timer = TimerData::new(secs(90));
+        timer.pause(true);
+        let now = SystemTime::now();
+
+        assert_eq!(TimeFormat::Clock.remaining_at(&timer, now), "00:01:30");
+        assert_eq!(TimeFormat::Compact.remaining_at(&timer, now), "1m 30s");
+        assert_eq!(TimeFormat::Percent.remaining_at(&timer, now), "0%");
+        assert_eq!(TimeFormat::Compact.total(timer.end_after()), "1m 30s");
+        assert_eq!(TimeFormat::Days.total(timer.end_after()), "00:01:30");
+        assert_eq!(TimeFormat::EndsAt.total(timer.end_after()), "00:01:30");
+
+        // A paused timer has no end time, & a finished one has already ended.
+        assert_eq!(
+            TimeFormat::EndsAt.remaining_at(&timer, now),
+            "00:01:30 left"
+        );
+        timer.pause(false);
+        assert_eq!(
+            TimeFormat::EndsAt.remaining_at(&timer, now),
+            ends_at(LocalTime::at(now + timer.remaining()))
+        );
+        timer.remove_time(secs(90));
+        assert_eq!(TimeFormat::EndsAt.remaining_at(&timer, now), "ended");
+        assert_eq!(TimeFormat::Percent.remaining_at(&timer, now), "100%");
+    }
+
+    #[test]
+    fn overtime() {
+        // The timer ended 90 seconds ago.
+        let timer: TimerData = ron::from_str(
+            "(duration: (secs: 150, nanos: 0), end_after: (secs: 60, nanos: 0), paused: false, \
+            acknowledged: true, overtime: true)",
+        )
+        .expect("Timer is valid");
+        let now = SystemTime::now();
+
+        assert_eq!(TimeFormat::Clock.remaining_at(&timer, now), "+00:01:30");
+        assert_eq!(TimeFormat::Compact.remaining_at(&timer, now), "+1m 30s");
+        assert_eq!(TimeFormat::Tenths.remaining_at(&timer, now), "+01:30.0");
+        assert_eq!(TimeFormat::Days.remaining_at(&timer, now), "+00:01:30");
+        assert_eq!(TimeFormat::Percent.remaining_at(&timer, now), "+00:01:30");
+        assert_eq!(TimeFormat::EndsAt.remaining_at(&timer, now), "+00:01:30");
+        assert_eq!(TimeFormat::Clock.total(timer.end_after()), "00:01:00");
+    }
+}
@@ -1,14 +1,24 @@
-use std::{io::ErrorKind, net::TcpStream, time::Duration};
+use std::{io::ErrorKind, time::Duration};
 
 use egui::Widget;
 use serde::{Deserialize, Serialize};
 
 use crate::{
+    clock::LocalTime,
     comms::{
         GuiAction, GuiResponse,
         sync_socket::{ReadError, ReadObj as _, WriteObj as _},
+        timer_sync::{Replica, TimerCommand, TimerId},
     },
-    gui::timer::{Timer, TimerData},
+    dnd::{DoNotDisturb, QuietHours},
+    format::TimeFormat,
+    gui::{
+        connection::Connection,
+        layout::{Grid, View, ZOOM_RANGE, ZOOM_STEP, list_radius},
+        timer::Timer,
+    },
+    shortcuts::{Shortcut, Shortcuts},
+    timer::{Appearance, Colour, KeepAwake, Preset, TimerData},
 };
 
 /// The key that persistent data is saved at.
@@ -16,36 +26,330 @@ const APP_KEY: &str = "GUI_TIMER";
 
 pub(crate) struct Gui {
     /// The connection to the tray.
-    connection: TcpStream,
+    connection: Connection,
     /// Whether the GUI is in the process of closing.
-    is_closing: Closing,
+    is_closing: bool,
+
+    /// The GUI's copy of the timers held by the tray.
+    timers: Replica,
+    /// The presets timers can be started from, as edited by the user.
+    presets: Vec<Preset>,
+    /// Whether the user has changed the presets without saving them.
+    presets_edited: bool,
+    /// When the user is not alerted that timers have finished.
+    dnd: DoNotDisturb,
+    /// The keys the user would like to trigger each keyboard shortcut, as edited by the user.
+    shortcuts: Shortcuts,
+    /// Whether the user has changed the shortcuts without saving them.
+    shortcuts_edited: bool,
+    /// How times are shown, unless a timer has its own format.
+    time_format: TimeFormat,
+    /// How long the timers that have been removed counted past their ends.
+    removed_overrun: Duration,
 
     /// Persistent GUI data.
     persistent: Persistent,
 }
 
 impl Gui {
-    pub fn new(cc: &eframe::CreationContext<'_>, connection: TcpStream) -> Self {
+    pub fn new(cc: &eframe::CreationContext<'_>, connection: Connection) -> Self {
         let mut persistent: Persistent = cc
             .storage
-            .map(|storage| eframe::get_value(storage, APP_KEY))
-            .flatten()
+            .and_then(|storage| eframe::get_value(storage, APP_KEY))
             .unwrap_or_default();
-
-        // TODO(tye): remove after debugging.
-        persistent.timer_data = vec![TimerData::new(Duration::from_secs(3))];
+        persistent.zoom = persistent
+            .zoom
+            .clamp(*ZOOM_RANGE.start(), *ZOOM_RANGE.end());
 
         Self {
             connection,
-            is_closing: Closing::No,
+            is_closing: false,
+            timers: Replica::default(),
+            presets: Vec::new(),
+            presets_edited: false,
+            dnd: DoNotDisturb::default(),
+            shortcuts: Shortcuts::default(),
+            shortcuts_edited: false,
+            time_format: TimeFormat::default(),
+            removed_overrun: Duration::ZERO,
             persistent,
         }
     }
 
+    /// Sends the response to the tray.
+    fn send(&mut self, response: GuiResponse) {
+        log::debug!("Gui Sent : {response:?}");
+
+        if let Err(err) = self.connection.write_obj(&response) {
+            log::error!("Unable to send {response:?} to tray: {err}");
+        }
+    }
+
+    /// Shows the timers, followed by the controls for adding timers & the settings.
+    fn main_ui(&mut self, ui: &mut egui::Ui, responses: &mut Vec<GuiResponse>) {
+        self.timers_ui(ui, responses);
+        ui.separator();
+
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::DragValue::new(&mut self.persistent.new_timer_minutes)
+                    .range(1..=24 * 60)
+                    .suffix(" min"),
+            );
+            if ui.button("Add").clicked() {
+                let minutes = self.persistent.new_timer_minutes;
+                let end_after = Duration::from_secs(minutes * 60);
+                responses.push(GuiResponse::Timer(TimerCommand::Create(end_after)));
+            }
+        });
+
+        ui.collapsing("Presets", |ui| self.presets_ui(ui, responses));
+        ui.collapsing("Do not disturb", |ui| self.dnd_ui(ui, responses));
+        ui.collapsing("Keyboard shortcuts", |ui| self.shortcuts_ui(ui, responses));
+        ui.collapsing("Time past the end", |ui| self.overrun_ui(ui, responses));
+    }
+
+    /// Shows every timer, laid out as the user chose.
+    fn timers_ui(&mut self, ui: &mut egui::Ui, responses: &mut Vec<GuiResponse>) {
+        let zoom = self.persistent.zoom;
+        let format = self.time_format;
+        let mut timers: Vec<_> = self.timers.iter_mut().collect();
+
+        match self.persistent.view {
+            View::Grid => {
+                let grid = Grid::fit(ui.available_width(), zoom);
+                for row in timers.chunks_mut(grid.columns) {
+                    ui.horizontal_top(|ui| {
+                        // Each cell already has space around its ring.
+                        ui.spacing_mut().item_spacing.x = 0.0;
+
+                        for (id, timer_data) in row {
+                            let size = egui::vec2(grid.cell_width(), 0.0);
+                            let layout = egui::Layout::top_down(egui::Align::Center);
+                            ui.allocate_ui_with_layout(size, layout, |ui| {
+                                ui.set_width(grid.cell_width());
+                                Timer::new(timer_data)
+                                    .radius(grid.radius)
+                                    .format(format)
+                                    .ui(ui);
+                                timer_controls(ui, *id, timer_data, responses);
+                            });
+                        }
+                    });
+                }
+            }
+            View::List => {
+                for (id, timer_data) in timers {
+                    ui.horizontal(|ui| {
+                        Timer::new(timer_data)
+                            .radius(list_radius(zoom))
+                            .format(format)
+                            .ui(ui);
+                        ui.vertical(|ui| timer_controls(ui, id, timer_data, responses));
+                    });
+                    ui.separator();
+                }
+            }
+        }
+    }
+
+    /// Allows the user to choose how the timers are laid out, & how large they are.
+    fn toolbar_ui(&mut self, ui: &mut egui::Ui, responses: &mut Vec<GuiResponse>) {
+        let persistent = &mut self.persistent;
+
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut persistent.view, View::Grid, "Grid");
+            ui.selectable_value(&mut persistent.view, View::List, "List");
+            ui.separator();
+
+            let zoom = &mut persistent.zoom;
+            let zoom_out = ui.add_enabled(*zoom > *ZOOM_RANGE.start(), egui::Button::new("−"));
+            if zoom_out.on_hover_text("Zoom out").clicked() {
+                *zoom = (*zoom - ZOOM_STEP).max(*ZOOM_RANGE.start());
+            }
+            let reset = ui.button(format!("{:.0}%", *zoom * 100.0));
+            if reset.on_hover_text("Reset zoom").clicked() {
+                *zoom = 1.0;
+            }
+            let zoom_in = ui.add_enabled(*zoom < *ZOOM_RANGE.end(), egui::Button::new("+"));
+            if zoom_in.on_hover_text("Zoom in").clicked() {
+                *zoom = (*zoom + ZOOM_STEP).min(*ZOOM_RANGE.end());
+            }
+            ui.separator();
+
+            let time_format = &mut self.time_format;
+            egui::ComboBox::from_id_salt("time_format")
+                .selected_text(time_format.name())
+                .show_ui(ui, |ui| {
+                    for format in TimeFormat::ALL {
+                        if ui
+                            .selectable_value(time_format, format, format.name())
+                            .changed()
+                        {
+                            responses.push(GuiResponse::TimeFormat(format));
+                        }
+                    }
+                })
+                .response
+                .on_hover_text("How times are shown");
+        });
+    }
+
+    /// Shows the presets timers can be started from, allowing the user to edit them.
+    fn presets_ui(&mut self, ui: &mut egui::Ui, responses: &mut Vec<GuiResponse>) {
+        let mut remove = None;
+
+        for (index, preset) in self.presets.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                let name =
+                    ui.add(egui::TextEdit::singleline(&mut preset.name).desired_width(100.0));
+
+                let mut minutes = preset.end_after.as_secs() / 60;
+                let length = ui.add(
+                    egui::DragValue::new(&mut minutes)
+                        .range(1..=24 * 60)
+                        .suffix(" min"),
+                );
+                // Presets from the tray's file may not be whole minutes, so they are only rounded once edited.
+                if length.changed() {
+                    preset.end_after = Duration::from_secs(minutes * 60);
+                }
+
+                self.presets_edited |= name.changed() || length.changed();
+
+                if ui.button("Start").clicked() {
+                    responses.push(GuiResponse::Timer(TimerCommand::Create(preset.end_after)));
+                }
+                if ui.button("Delete").clicked() {
+                    remove = Some(index);
+                }
+            });
+        }
+
+        if let Some(index) = remove {
+            self.presets.remove(index);
+            self.presets_edited = true;
+        }
+
+        ui.horizontal(|ui| {
+            if ui.button("New preset").clicked() {
+                self.presets.push(Preset {
+                    name: "Timer".into(),
+                    end_after: Duration::from_secs(self.persistent.new_timer_minutes * 60),
+                });
+                self.presets_edited = true;
+            }
+
+            if ui
+                .add_enabled(self.presets_edited, egui::Button::new("Save presets"))
+                .clicked()
+            {
+                responses.push(GuiResponse::Presets(self.presets.clone()));
+                self.presets_edited = false;
+            }
+        });
+    }
+
+    /// Shows when the user is not alerted that timers have finished, allowing the user to change it.
+    fn dnd_ui(&mut self, ui: &mut egui::Ui, responses: &mut Vec<GuiResponse>) {
+        let mut changed = ui.checkbox(&mut self.dnd.enabled, "On").changed();
+
+        let mut scheduled = self.dnd.quiet_hours.is_some();
+        if ui.checkbox(&mut scheduled, "Quiet hours").changed() {
+            self.dnd.quiet_hours = scheduled.then(QuietHours::default);
+            changed = true;
+        }
+
+        if let Some(quiet_hours) = &mut self.dnd.quiet_hours {
+            ui.horizontal(|ui| {
+                ui.label("From");
+                changed |= time_ui(ui, &mut quiet_hours.start);
+                ui.label("to");
+                changed |= time_ui(ui, &mut quiet_hours.end);
+            });
+        }
+
+        if self.dnd.active() {
+            ui.label("Finished timers will not alert you.");
+        }
+
+        if changed {
+            responses.push(GuiResponse::DoNotDisturb(self.dnd.clone()));
+        }
+    }
+
+    /// Shows the keys that trigger each keyboard shortcut, allowing the user to change them.
+    fn shortcuts_ui(&mut self, ui: &mut egui::Ui, responses: &mut Vec<GuiResponse>) {
+        egui::Grid::new("shortcuts").show(ui, |ui| {
+            for shortcut in Shortcut::ALL {
+                ui.label(shortcut.description());
+                let trigger = ui.add(
+                    egui::TextEdit::singleline(self.shortcuts.trigger_mut(shortcut))
+                        .desired_width(100.0),
+                );
+                self.shortcuts_edited |= trigger.changed();
+                ui.end_row();
+            }
+        });
+
+        ui.label("Your desktop may ask you to confirm these, or choose different keys.");
+
+        if ui
+            .add_enabled(self.shortcuts_edited, egui::Button::new("Save shortcuts"))
+            .clicked()
+        {
+            responses.push(GuiResponse::Shortcuts(self.shortcuts.clone()));
+            self.shortcuts_edited = false;
+        }
+    }
+
+    /// Shows how long each timer has counted past its end, allowing the user to forget it.
+    fn overrun_ui(&mut self, ui: &mut egui::Ui, responses: &mut Vec<GuiResponse>) {
+        let format = self.time_format;
+        let mut total = self.removed_overrun;
+
+        egui::Grid::new("overrun").show(ui, |ui| {
+            for (index, (id, timer_data)) in self.timers.iter_mut().enumerate() {
+                let overrun = timer_data.recorded_overrun();
+                total += overrun;
+
+                ui.label(
+                    timer_data
+                        .appearance()
+                        .name(|| format!("Timer {}", index + 1)),
+                );
+                ui.label(timer_data.time_format().unwrap_or(format).overrun(overrun));
+                if ui
+                    .add_enabled(!overrun.is_zero(), egui::Button::new("Forget"))
+                    .clicked()
+                {
+                    responses.push(GuiResponse::Timer(TimerCommand::ForgetOverrun(id)));
+                }
+                ui.end_row();
+            }
+
+            if !self.removed_overrun.is_zero() {
+                ui.label("Removed timers");
+                ui.label(format.overrun(self.removed_overrun));
+                ui.end_row();
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.label(format!("{} in total", format.overrun(total)));
+            if ui
+                .add_enabled(!total.is_zero(), egui::Button::new("Forget all"))
+                .clicked()
+            {
+                responses.push(GuiResponse::ForgetOverrun);
+            }
+        });
+    }
+
     /// Reads the action from the tray if there is one.
     fn read_action(&mut self) -> Option<GuiAction> {
         // Otherwise there is an error trying to read from the connection.
-        if self.is_closing != Closing::No {
+        if self.is_closing {
             return None;
         }
 
@@ -62,36 +366,64 @@ impl Gui {
 }
 
 impl eframe::App for Gui {
-    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        let mut responses = Vec::new();
+
+        egui::TopBottomPanel::top("toolbar").show(ctx, |ui| self.toolbar_ui(ui, &mut responses));
+
         egui::CentralPanel::default().show(ctx, |ui| {
-            for timer_data in self.persistent.timer_data.iter_mut() {
-                Timer::new(timer_data).radius(50.0).ui(ui);
+            if !self.timers.is_synced() {
+                ui.spinner();
+                return;
             }
 
-            if ui.button("Reset").clicked() {
-                let _ = self.persistent.timer_data.pop();
-                self.persistent
-                    .timer_data
-                    .push(TimerData::new(Duration::from_secs(5)));
-            }
+            egui::ScrollArea::vertical()
+                .auto_shrink(false)
+                .show(ui, |ui| self.main_ui(ui, &mut responses));
         });
+        for response in responses {
+            self.send(response);
+        }
 
-        // Ensure the GUI still updates when the user is not interacting with it.
-        ctx.request_repaint_after(Duration::from_millis(250));
+        // Ensure the GUI still updates when the user is not interacting with it, often enough to show tenths of a
+        // second when they are counting down.
+        let global_format = self.time_format;
+        let tenths = self.timers.iter_mut().any(|(_, timer_data)| {
+            !timer_data.paused()
+                && timer_data.time_format().unwrap_or(global_format) == TimeFormat::Tenths
+        });
+        ctx.request_repaint_after(Duration::from_millis(if tenths { 50 } else { 250 }));
 
         // Execute on any sent actions.
-        if let Some(action) = self.read_action() {
+        while let Some(action) = self.read_action() {
             log::debug!("Gui Received : {action:?}");
 
             match action {
-                GuiAction::Close => {
-                    self.is_closing = Closing::Inform;
+                GuiAction::Close | GuiAction::Quit => {
+                    self.is_closing = true;
                     ctx.send_viewport_cmd(egui::ViewportCommand::Close)
                 }
-                GuiAction::Quit => {
-                    self.is_closing = Closing::Silent;
-                    ctx.send_viewport_cmd(egui::ViewportCommand::Close)
+                GuiAction::Sync(message) => {
+                    if let Err(gap) = self.timers.apply(message) {
+                        log::warn!("Missed timer update: {gap}");
+                        self.send(GuiResponse::Resync);
+                    }
+                }
+                GuiAction::Presets(presets) => {
+                    // Keep the user's unsaved changes.
+                    if !self.presets_edited {
+                        self.presets = presets;
+                    }
+                }
+                GuiAction::DoNotDisturb(dnd) => self.dnd = dnd,
+                GuiAction::Shortcuts(shortcuts) => {
+                    // Keep the user's unsaved changes.
+                    if !self.shortcuts_edited {
+                        self.shortcuts = shortcuts;
+                    }
                 }
+                GuiAction::TimeFormat(format) => self.time_format = format,
+                GuiAction::RemovedOverrun(overrun) => self.removed_overrun = overrun,
             }
         }
     }
@@ -100,32 +432,220 @@ impl eframe::App for Gui {
         eframe::set_value(storage, APP_KEY, &self.persistent);
     }
 
+    /// Called after the GUI's state has been saved, so the tray knows it is safe to quit.
     fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
-        if self.is_closing == Closing::Silent {
-            return;
+        self.send(GuiResponse::Closed);
+    }
+}
+
+/// The buttons for controlling a single timer, with its less common options in a menu.
+fn timer_controls(
+    ui: &mut egui::Ui,
+    id: TimerId,
+    timer_data: &mut TimerData,
+    responses: &mut Vec<GuiResponse>,
+) {
+    ui.horizontal_wrapped(|ui| {
+        if timer_data.needs_attention() && ui.button("Acknowledge").clicked() {
+            responses.push(GuiResponse::Timer(TimerCommand::Acknowledge(id)));
+        }
+        let paused = timer_data.paused();
+        if ui.button(if paused { "Resume" } else { "Pause" }).clicked() {
+            responses.push(GuiResponse::Timer(TimerCommand::Pause(id, !paused)));
+        }
+        if ui.button("Reset").clicked() {
+            responses.push(GuiResponse::Timer(TimerCommand::Reset(id)));
+        }
+        let mut pause_when_away = timer_data.pause_when_away();
+        if ui
+            .checkbox(&mut pause_when_away, "Pause when away")
+            .changed()
+        {
+            responses.push(GuiResponse::Timer(TimerCommand::PauseWhenAway(
+                id,
+                pause_when_away,
+            )));
+        }
+        let mut overtime = timer_data.overtime();
+        if ui
+            .checkbox(&mut overtime, "Keep counting after it ends")
+            .changed()
+        {
+            responses.push(GuiResponse::Timer(TimerCommand::Overtime(id, overtime)));
+        }
+        if ui.button("Delete").clicked() {
+            responses.push(GuiResponse::Timer(TimerCommand::Remove(id)));
+        }
+
+        let more = ui.menu_button("More", |ui| {
+            let mut appearance = timer_data.appearance().clone();
+            let finished = appearance_ui(ui, id, &mut appearance);
+            if appearance != *timer_data.appearance() {
+                // Shown straight away, as the tray is only told once the user has finished editing.
+                timer_data.set_appearance(appearance.clone());
+            }
+            if finished {
+                responses.push(GuiResponse::Timer(TimerCommand::Appearance(id, appearance)));
+            }
+
+            ui.separator();
+            let mut time_format = timer_data.time_format();
+            ui.menu_button("Time format", |ui| {
+                ui.radio_value(&mut time_format, None, "Same as other timers");
+                for format in TimeFormat::ALL {
+                    ui.radio_value(&mut time_format, Some(format), format.name());
+                }
+            });
+            if time_format != timer_data.time_format() {
+                responses.push(GuiResponse::Timer(TimerCommand::TimeFormat(
+                    id,
+                    time_format,
+                )));
+            }
+        });
+
+        // Closing the menu whilst typing doesn't make the field lose focus, so the edit is sent here instead.
+        if more.inner.is_none() {
+            let typed = ui.data_mut(|data| data.remove_temp::<bool>(appearance_typed_id(id)));
+            if typed == Some(true) {
+                let appearance = timer_data.appearance().clone();
+                responses.push(GuiResponse::Timer(TimerCommand::Appearance(id, appearance)));
+            }
         }
+    });
+
+    if let Some(keep_awake) = keep_awake_ui(ui, id, timer_data.keep_awake()) {
+        responses.push(GuiResponse::Timer(TimerCommand::KeepAwake(id, keep_awake)));
+    }
 
-        let _ = self
-            .connection
-            .write_obj(GuiResponse::Closed)
-            .inspect_err(|err| log::error!("Unable to inform tray of GUI close: {err}"));
+    let away = timer_data.away().as_secs() / 60;
+    if away > 0 {
+        ui.label(format!("You were away for {away} min of this timer."));
+    }
+}
+
+/// Allows the user to name a timer & choose its colour, returning whether they have finished a change.
+///
+/// Typing in a field is finished when the field loses focus or Enter is pressed, whilst choosing a colour is finished
+/// straight away.
+fn appearance_ui(ui: &mut egui::Ui, id: TimerId, appearance: &mut Appearance) -> bool {
+    let (label, emoji) = egui::Grid::new("appearance")
+        .show(ui, |ui| {
+            ui.label("Label");
+            let label =
+                ui.add(egui::TextEdit::singleline(&mut appearance.label).desired_width(120.0));
+            ui.end_row();
+
+            ui.label("Emoji");
+            let emoji =
+                ui.add(egui::TextEdit::singleline(&mut appearance.emoji).desired_width(30.0));
+            ui.end_row();
+
+            (label, emoji)
+        })
+        .inner;
+
+    let typed_id = appearance_typed_id(id);
+    let typed = ui.data_mut(|data| {
+        let typed = data.get_temp_mut_or_default::<bool>(typed_id);
+        *typed |= label.changed() || emoji.changed();
+        *typed
+    });
+    let mut finished = typed && (label.lost_focus() || emoji.lost_focus());
+
+    ui.horizontal(|ui| {
+        for colour in Colour::PALETTE {
+            let [red, green, blue] = colour.rgb();
+            let swatch = egui::Button::new("")
+                .fill(egui::Color32::from_rgb(red, green, blue))
+                .min_size(egui::vec2(18.0, 18.0))
+                .selected(appearance.colour == colour);
+            if ui
+                .add(swatch)
+                .on_hover_text(format!("{colour:?}"))
+                .clicked()
+            {
+                appearance.colour = colour;
+                finished = true;
+            }
+        }
+    });
 
-        log::debug!("Gui Sent : {:?}", GuiResponse::Closed);
+    if finished {
+        ui.data_mut(|data| data.remove::<bool>(typed_id));
     }
+    finished
+}
+
+/// Where [`appearance_ui`] remembers whether a timer's fields have been typed in since the tray was last told.
+fn appearance_typed_id(id: TimerId) -> egui::Id {
+    egui::Id::new(("appearance_typed", id))
+}
+
+/// Allows the user to choose when a timer keeps the computer awake, returning their choice if it changed.
+fn keep_awake_ui(ui: &mut egui::Ui, id: TimerId, keep_awake: KeepAwake) -> Option<KeepAwake> {
+    let mut choice = keep_awake;
+
+    ui.horizontal(|ui| {
+        ui.label("Keep computer awake");
+        egui::ComboBox::from_id_salt(("keep_awake", id))
+            .selected_text(match choice {
+                KeepAwake::Never => "Never",
+                KeepAwake::WhileRunning => "Whilst running",
+                KeepAwake::Final(_) => "For the final",
+            })
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut choice, KeepAwake::Never, "Never");
+                ui.selectable_value(&mut choice, KeepAwake::WhileRunning, "Whilst running");
+                if !matches!(choice, KeepAwake::Final(_)) {
+                    let final_minutes = KeepAwake::Final(Duration::from_secs(5 * 60));
+                    ui.selectable_value(&mut choice, final_minutes, "For the final");
+                }
+            });
+
+        if let KeepAwake::Final(window) = &mut choice {
+            let mut minutes = window.as_secs() / 60;
+            ui.add(
+                egui::DragValue::new(&mut minutes)
+                    .range(1..=24 * 60)
+                    .suffix(" min"),
+            );
+            *window = Duration::from_secs(minutes * 60);
+        }
+    });
+
+    (choice != keep_awake).then_some(choice)
+}
+
+/// Allows the user to edit a time of day, returning whether it changed.
+fn time_ui(ui: &mut egui::Ui, time: &mut LocalTime) -> bool {
+    let hour = ui.add(egui::DragValue::new(&mut time.hour).range(0..=23));
+    ui.label(":");
+    let minute = ui.add(
+        egui::DragValue::new(&mut time.minute)
+            .range(0..=59)
+            .custom_formatter(|minute, _| format!("{minute:0>2}")),
+    );
+    hour.changed() || minute.changed()
 }
 
-#[derive(Deserialize, Serialize, Default)]
+#[derive(Deserialize, Serialize)]
+#[serde(default)]
 struct Persistent {
-    timer_data: Vec<TimerData>,
+    /// The length of the timer created with the "Add" button.
+    new_timer_minutes: u64,
+    /// How the timers are shown.
+    view: View,
+    /// How large the timers are shown, relative to their default size.
+    zoom: f32,
 }
 
-/// How the GUI is closing.
-#[derive(Clone, Copy, PartialEq)]
-enum Closing {
-    /// The GUI is not closing.
-    No,
-    /// The GUI is closing and will inform the tray.
-    Inform,
-    /// The GUI is closing and **will not** inform the tray.
-    Silent,
+impl Default for Persistent {
+    fn default() -> Self {
+        Self {
+            new_timer_minutes: 5,
+            view: View::default(),
+            zoom: 1.0,
+        }
+    }
 }
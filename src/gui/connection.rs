@@ -0,0 +1,90 @@
+use std::{
+    io::{Read, Write},
+    net::TcpStream,
+    os::unix::net::UnixStream,
+};
+
+use crate::comms::{
+    SOCKET_ADDR, TransportKind, socket_path,
+    transport::{PipeTransport, TcpTransport, Transport as _, UnixTransport},
+};
+
+/// The connection from the GUI to the tray.
+pub(crate) enum Connection {
+    Tcp(TcpStream),
+    Unix(UnixStream),
+    Pipe(UnixStream),
+}
+
+impl Connection {
+    /// Connects to the tray using the given [`TransportKind`].
+    pub fn connect(transport: TransportKind) -> std::io::Result<Self> {
+        // The connection is established with the async transport, then used synchronously by the GUI.
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_io()
+            .build()?;
+
+        runtime.block_on(async {
+            let connection = match transport {
+                TransportKind::Tcp => {
+                    let stream = TcpTransport::new(SOCKET_ADDR).connect().await?;
+                    Self::Tcp(stream.into_std()?)
+                }
+                TransportKind::Unix => {
+                    let stream = UnixTransport::new(socket_path()).connect().await?;
+                    Self::Unix(stream.into_std()?)
+                }
+                TransportKind::Pipe => {
+                    let stream = PipeTransport::default().connect().await?;
+                    Self::Pipe(stream.into_std()?)
+                }
+            };
+
+            Ok(connection)
+        })
+    }
+
+    /// Creates another handle to the same connection.
+    pub fn try_clone(&self) -> std::io::Result<Self> {
+        Ok(match self {
+            Connection::Tcp(stream) => Connection::Tcp(stream.try_clone()?),
+            Connection::Unix(stream) => Connection::Unix(stream.try_clone()?),
+            Connection::Pipe(stream) => Connection::Pipe(stream.try_clone()?),
+        })
+    }
+
+    /// Moves this connection into or out of non-blocking mode.
+    pub fn set_nonblocking(&self, nonblocking: bool) -> std::io::Result<()> {
+        match self {
+            Connection::Tcp(stream) => stream.set_nonblocking(nonblocking),
+            Connection::Unix(stream) | Connection::Pipe(stream) => {
+                stream.set_nonblocking(nonblocking)
+            }
+        }
+    }
+}
+
+impl Read for Connection {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Connection::Tcp(stream) => stream.read(buf),
+            Connection::Unix(stream) | Connection::Pipe(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Connection {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Connection::Tcp(stream) => stream.write(buf),
+            Connection::Unix(stream) | Connection::Pipe(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Connection::Tcp(stream) => stream.flush(),
+            Connection::Unix(stream) | Connection::Pipe(stream) => stream.flush(),
+        }
+    }
+}
@@ -0,0 +1,112 @@
+//! How the timers are arranged in the GUI.
+
+use serde::{Deserialize, Serialize};
+
+/// The radius of a timer's ring at the default zoom.
+const BASE_RADIUS: f32 = 50.0;
+
+/// The smallest a ring is shrunk to in a narrow window.
+const MIN_RADIUS: f32 = 20.0;
+
+/// The space around each ring in the grid, for its outline & the gap between rings.
+const CELL_PADDING: f32 = 16.0;
+
+/// How far each press of the zoom buttons zooms in or out.
+pub const ZOOM_STEP: f32 = 0.25;
+
+/// The range the user can zoom within.
+pub const ZOOM_RANGE: std::ops::RangeInclusive<f32> = 0.5..=3.0;
+
+/// How the timers are shown.
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Debug, Default)]
+pub enum View {
+    /// Large rings, wrapping onto as many rows as needed.
+    #[default]
+    Grid,
+    /// One timer per row, with a small ring beside its controls.
+    List,
+}
+
+/// The columns of the grid, & the size of the rings within them.
+#[derive(PartialEq, Debug)]
+pub struct Grid {
+    pub columns: usize,
+    pub radius: f32,
+}
+
+impl Grid {
+    /// Fits as many rings of the zoomed size as possible into the width, then grows the rings slightly to fill any
+    /// space left over.
+    ///
+    /// When even a single ring does not fit, it is shrunk to the width of the window.
+    pub fn fit(width: f32, zoom: f32) -> Self {
+        let preferred = BASE_RADIUS * zoom;
+        let cell = preferred * 2.0 + CELL_PADDING;
+        let columns = ((width / cell).floor() as usize).max(1);
+
+        let radius =
+            ((width / columns as f32 - CELL_PADDING) / 2.0).clamp(MIN_RADIUS, preferred * 1.25);
+        Self { columns, radius }
+    }
+
+    /// The width given to each timer.
+    pub fn cell_width(&self) -> f32 {
+        self.radius * 2.0 + CELL_PADDING
+    }
+}
+
+/// The radius of the rings in the list view.
+pub fn list_radius(zoom: f32) -> f32 {
+    BASE_RADIUS * zoom * 0.8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BASE_RADIUS, Grid, MIN_RADIUS};
+
+    #[test]
+    fn grid_fits_width() {
+        // Exactly three rings of the default size.
+        let grid = Grid::fit(3.0 * 116.0, 1.0);
+        assert_eq!(
+            grid,
+            Grid {
+                columns: 3,
+                radius: BASE_RADIUS
+            }
+        );
+
+        // Leftover space grows the rings, without adding a column.
+        let grid = Grid::fit(3.0 * 116.0 + 60.0, 1.0);
+        assert_eq!(grid.columns, 3);
+        assert_eq!(grid.radius, BASE_RADIUS + 10.0);
+        assert!(grid.cell_width() * 3.0 <= 3.0 * 116.0 + 60.0);
+
+        // Zooming in fits fewer, larger rings.
+        let grid = Grid::fit(3.0 * 116.0, 2.0);
+        assert_eq!(grid.columns, 1);
+        assert_eq!(grid.radius, 2.0 * BASE_RADIUS * 1.25);
+    }
+
+    #[test]
+    fn grid_shrinks_in_narrow_window() {
+        let grid = Grid::fit(60.0, 1.0);
+        assert_eq!(
+            grid,
+            Grid {
+                columns: 1,
+                radius: 22.0
+            }
+        );
+
+        // But never so small the ring cannot be read.
+        let grid = Grid::fit(10.0, 1.0);
+        assert_eq!(
+            grid,
+            Grid {
+                columns: 1,
+                radius: MIN_RADIUS
+            }
+        );
+    }
+}
@@ -1,24 +1,34 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")] // hide console window on Windows in release
 
-use std::net::TcpStream;
-
-use crate::comms::{GuiResponse, SOCKET_ADDR, sync_socket::WriteObj};
+use crate::comms::{GuiResponse, TransportKind, sync_socket::WriteObj};
 use app::Gui;
+use connection::Connection;
+use std::sync::Mutex;
 
 mod app;
+mod connection;
+mod layout;
+mod prompt;
 mod timer;
 
-pub(crate) fn launch_gui() {
-    let mut connection = TcpStream::connect(SOCKET_ADDR)
-        .expect(&format!("Unable to connect to tray on {SOCKET_ADDR}"));
+pub(crate) use prompt::launch_prompt;
+
+pub(crate) fn launch_gui(transport: TransportKind) {
+    let mut connection = Connection::connect(transport)
+        .unwrap_or_else(|err| panic!("Unable to connect to tray with {transport:?}: {err}"));
     connection
         .set_nonblocking(true)
-        .expect("Unable to set tcp connection to non-blocking");
+        .expect("Unable to set connection to non-blocking");
 
     connection
         .write_obj(GuiResponse::Opened)
         .expect("Unable to inform tray of GUI open");
 
+    match connection.try_clone() {
+        Ok(connection) => report_panics(connection),
+        Err(err) => log::warn!("Unable to report crashes to tray: {err}"),
+    }
+
     eframe::run_native(
         "Gui Timer",
         eframe::NativeOptions::default(),
@@ -26,3 +36,20 @@ pub(crate) fn launch_gui() {
     )
     .expect("Unable to start GUI");
 }
+
+/// Tells the tray when the GUI panics, before it dies.
+fn report_panics(connection: Connection) {
+    let connection = Mutex::new(connection);
+    let default_hook = std::panic::take_hook();
+
+    std::panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+
+        // The connection is not used again if this panicked whilst it was locked.
+        let Ok(mut connection) = connection.try_lock() else {
+            return;
+        };
+        let _ = connection.set_nonblocking(false);
+        let _ = connection.write_obj(GuiResponse::Crashed(info.to_string()));
+    }));
+}
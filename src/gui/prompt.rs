@@ -0,0 +1,73 @@
+use std::time::Duration;
+
+/// Asks the user for the length of a new timer with a small window.
+///
+/// The length is printed to stdout in seconds. Nothing is printed if the user cancels.
+pub(crate) fn launch_prompt() {
+    let options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default()
+            .with_inner_size([260.0, 80.0])
+            .with_resizable(false),
+        ..Default::default()
+    };
+
+    eframe::run_native(
+        "New Timer",
+        options,
+        Box::new(|_| Ok(Box::new(Prompt::default()))),
+    )
+    .expect("Unable to start prompt");
+}
+
+/// The window asking for the length of a new timer.
+struct Prompt {
+    minutes: u64,
+    seconds: u64,
+}
+
+impl Default for Prompt {
+    fn default() -> Self {
+        Self {
+            minutes: 10,
+            seconds: 0,
+        }
+    }
+}
+
+impl eframe::App for Prompt {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::DragValue::new(&mut self.minutes)
+                        .range(0..=24 * 60)
+                        .suffix(" min"),
+                );
+                ui.add(
+                    egui::DragValue::new(&mut self.seconds)
+                        .range(0..=59)
+                        .suffix(" s"),
+                );
+            });
+
+            ui.horizontal(|ui| {
+                let length = Duration::from_secs(self.minutes * 60 + self.seconds);
+                let start = ui
+                    .add_enabled(!length.is_zero(), egui::Button::new("Start"))
+                    .clicked()
+                    || (!length.is_zero() && ui.input(|input| input.key_pressed(egui::Key::Enter)));
+
+                if start {
+                    println!("{}", length.as_secs());
+                    ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+                }
+
+                if ui.button("Cancel").clicked()
+                    || ui.input(|input| input.key_pressed(egui::Key::Escape))
+                {
+                    ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+                }
+            });
+        });
+    }
+}
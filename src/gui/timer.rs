@@ -1,60 +1,22 @@
-use std::{
-    ops::Add,
-    time::{Duration, Instant},
-};
-
 use egui::{Align2, Color32, Pos2, Shape, Stroke, Ui, Widget, WidgetInfo, WidgetType, emath};
 
-/// Persistent data for a [`Timer`] to use between frames.
-/// This needs to be passed into the [`Timer`] on each frame.
-///
-/// This data can also be seralised and deserialised.
-#[derive(serde::Serialize, serde::Deserialize)]
-pub struct TimerData {
-    /// When the timer was last updated.
-    /// If this is None, then this is the first update.
-    #[serde(skip)]
-    last_ticked: Option<Instant>,
-    /// How much time has passed.
-    duration: Duration,
-    /// After how long will the timer end.
-    end_after: Duration,
-    /// Whether the timer is running.
-    paused: bool,
-}
-
-impl TimerData {
-    /// Create new [`TimerData`] with the given timer duration.
-    pub fn new(end_after: Duration) -> Self {
-        Self {
-            last_ticked: None,
-            duration: Duration::ZERO,
-            end_after,
-            paused: false,
-        }
-    }
-
-    /// Whether a [`Timer`] is puased.
-    pub fn pause(&mut self, pause: bool) {
-        self.paused = pause;
-    }
-
-    /// Sets the amount of time that has passed to 0.
-    pub fn reset(&mut self) {
-        self.duration = Duration::ZERO;
-    }
-}
+use crate::{format::TimeFormat, timer::TimerData};
 
 /// A circular progress bar to indicate an percentage of time remaining.
 pub struct Timer<'data> {
     radius: Option<f32>,
+    format: TimeFormat,
     data: &'data mut TimerData,
 }
 
 impl<'data> Timer<'data> {
     /// Creates a [`Timer`] with the persistent [`TimerData`].
     pub fn new(data: &'data mut TimerData) -> Self {
-        Self { radius: None, data }
+        Self {
+            radius: None,
+            format: TimeFormat::default(),
+            data,
+        }
     }
 
     /// Sets the radius of the timer that will be shown.
@@ -62,12 +24,19 @@ impl<'data> Timer<'data> {
         self.radius = Some(radius);
         self
     }
+
+    /// Sets how the times are shown, unless the timer has its own format.
+    pub fn format(mut self, format: TimeFormat) -> Self {
+        self.format = format;
+        self
+    }
 }
 
 impl<'data> Widget for Timer<'data> {
     fn ui(self, ui: &mut egui::Ui) -> egui::Response {
         ui.add(TimerWidget {
             radius: self.radius.unwrap_or(50.0),
+            format: self.data.time_format().unwrap_or(self.format),
             data: self.data,
         })
     }
@@ -76,6 +45,7 @@ impl<'data> Widget for Timer<'data> {
 /// Responsible for drawing the widget specified via a [`Timer`].
 struct TimerWidget<'data> {
     radius: f32,
+    format: TimeFormat,
     data: &'data mut TimerData,
 }
 
@@ -87,16 +57,9 @@ impl<'data> TimerWidget<'data> {
     /// The timer widget extends out by its [`radius`](Self::radius) in a circle.
     fn paint_at(self, ui: &Ui, position: Pos2) {
         // Increment timer
-        if !self.data.paused {
-            if let Some(last_tick) = self.data.last_ticked {
-                let elapsed = Instant::now() - last_tick;
-                self.data.duration = self.data.duration.add(elapsed).min(self.data.end_after);
-            }
-
-            self.data.last_ticked = Some(Instant::now());
-        }
+        self.data.tick();
 
-        let progress = self.data.duration.div_duration_f32(self.data.end_after);
+        let progress = self.data.progress();
         let points = 20;
 
         let outline_points: Vec<Pos2> = (0..=points)
@@ -128,32 +91,57 @@ impl<'data> TimerWidget<'data> {
             .collect();
 
         // Progress
+        let [red, green, blue] = self.data.appearance().colour.rgb();
         ui.painter().add(Shape::line(
             progress_points,
-            Stroke::new(3.0, Color32::LIGHT_BLUE),
+            Stroke::new(3.0, Color32::from_rgb(red, green, blue)),
         ));
 
-        // Remaining time.
-        let remaining = {
-            let secs = self.data.end_after.as_secs() - self.data.duration.as_secs();
-            let minuets = secs / 60;
-            let hours = minuets / 60;
-            format!("{hours:0>2}:{minuets:0>2}:{secs:0>2}")
-        };
+        // Overtime overflows the ring, lapping it again for each length of the timer.
+        let overrun = self.data.overrun();
+        if !overrun.is_zero() {
+            let laps = match self.data.end_after().is_zero() {
+                true => 1.0,
+                false => overrun.div_duration_f64(self.data.end_after()),
+            };
+            let lap = match laps.fract() {
+                0.0 => 1.0,
+                lap => lap,
+            };
+            let overrun_angle = emath::lerp(Self::START_ANGLE..=Self::END_ANGLE, lap);
+
+            let overrun_points: Vec<Pos2> = (0..=points)
+                .map(|i| {
+                    let angle =
+                        emath::lerp(Self::START_ANGLE..=overrun_angle, i as f64 / points as f64);
+                    let (sin, cos) = angle.sin_cos();
+                    position + self.radius * egui::vec2(cos as f32, sin as f32)
+                })
+                .collect();
+
+            ui.painter().add(Shape::line(
+                overrun_points,
+                Stroke::new(3.0, ui.visuals().warn_fg_color),
+            ));
+        }
 
-        let total = {
-            let secs = self.data.end_after.as_secs();
-            let minuets = secs / 60;
-            let hours = minuets / 60;
-            format!("{hours:0>2}:{minuets:0>2}:{secs:0>2}")
-        };
+        // Remaining time, or the overtime in a warning colour.
+        let remaining = self.format.remaining(self.data);
+        let total = self.format.total(self.data.end_after());
 
+        let text = match self.data.appearance().emoji.trim() {
+            "" => format!("{remaining}\n{total}"),
+            emoji => format!("{emoji}\n{remaining}\n{total}"),
+        };
         ui.painter().text(
             position,
             Align2::CENTER_CENTER,
-            format!("{remaining}\n{total}"),
+            text,
             egui::FontId::default(),
-            ui.visuals().text_color(),
+            match overrun.is_zero() {
+                true => ui.visuals().text_color(),
+                false => ui.visuals().warn_fg_color,
+            },
         );
     }
 }
@@ -161,10 +149,33 @@ impl<'data> TimerWidget<'data> {
 impl<'data> Widget for TimerWidget<'data> {
     fn ui(self, ui: &mut egui::Ui) -> egui::Response {
         let size = self.radius * 2.0;
-        let (rect, response) = ui.allocate_exact_size(egui::vec2(size, size), egui::Sense::empty());
+        let label = self.data.appearance().label.trim().to_string();
+
+        // The label is shown under the ring, within the width of the ring.
+        let galley = (!label.is_empty()).then(|| {
+            ui.painter().layout(
+                label.clone(),
+                egui::FontId::default(),
+                ui.visuals().strong_text_color(),
+                size,
+            )
+        });
+        let label_height = galley.as_ref().map_or(0.0, |galley| galley.size().y);
+
+        let (rect, response) =
+            ui.allocate_exact_size(egui::vec2(size, size + label_height), egui::Sense::empty());
+
+        let ring = egui::Rect::from_min_size(rect.min, egui::vec2(size, size));
+        self.paint_at(ui, ring.center());
+        if let Some(galley) = galley {
+            let position = egui::pos2(ring.center().x - galley.size().x / 2.0, ring.bottom());
+            ui.painter()
+                .galley(position, galley, ui.visuals().strong_text_color());
+        }
 
-        self.paint_at(ui, rect.center());
-        response.widget_info(|| WidgetInfo::new(WidgetType::ProgressIndicator));
+        response.widget_info(|| {
+            WidgetInfo::labeled(WidgetType::ProgressIndicator, ui.is_enabled(), &label)
+        });
 
         response
     }
@@ -0,0 +1,265 @@
+//! Installs the desktop integration for the current user, following the XDG base directory & desktop entry
+//! specifications.
+
+use std::{
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use crate::{comms::TransportKind, systemd};
+
+/// The name the desktop entries & icon are installed under.
+const NAME: &str = env!("CARGO_PKG_NAME");
+
+/// The icon bundled with the application.
+const ICON: &[u8] = include_bytes!("tray/icon.png");
+
+/// The size of the bundled [`ICON`].
+const ICON_SIZE: &str = "64x64";
+
+/// An error encountered whilst installing or uninstalling.
+#[derive(thiserror::Error, Debug)]
+pub enum InstallError {
+    #[error("Unable to find the user's home directory.")]
+    NoHome,
+    #[error("Unable to find the path of the executable: {0}")]
+    Exe(std::io::Error),
+    #[error("Unable to write '{path}': {source}")]
+    Write {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("Unable to remove '{path}': {source}")]
+    Remove {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+}
+
+/// The user's directories that files are installed into.
+pub struct Dirs {
+    /// `$XDG_CONFIG_HOME`, or `~/.config`.
+    pub config: PathBuf,
+    /// `$XDG_DATA_HOME`, or `~/.local/share`.
+    pub data: PathBuf,
+}
+
+impl Dirs {
+    /// Finds the user's directories from the environment.
+    pub fn from_env() -> Result<Self, InstallError> {
+        let home = std::env::var_os("HOME").map(PathBuf::from);
+        // Relative paths are invalid & must be ignored.
+        let var = |name: &str, fallback: &str| {
+            std::env::var_os(name)
+                .map(PathBuf::from)
+                .filter(|path| path.is_absolute())
+                .or_else(|| home.as_ref().map(|home| home.join(fallback)))
+                .ok_or(InstallError::NoHome)
+        };
+
+        Ok(Self {
+            config: var("XDG_CONFIG_HOME", ".config")?,
+            data: var("XDG_DATA_HOME", ".local/share")?,
+        })
+    }
+
+    /// The entry that starts the tray when the user logs in.
+    fn autostart(&self) -> PathBuf {
+        self.config
+            .join("autostart")
+            .join(format!("{NAME}.desktop"))
+    }
+
+    /// The entry that shows the application in the user's launcher.
+    fn launcher(&self) -> PathBuf {
+        self.data
+            .join("applications")
+            .join(format!("{NAME}.desktop"))
+    }
+
+    /// The user service that runs the tray.
+    fn service(&self) -> PathBuf {
+        self.config
+            .join("systemd/user")
+            .join(format!("{NAME}.service"))
+    }
+
+    /// The socket that starts the user service.
+    fn socket(&self) -> PathBuf {
+        self.config
+            .join("systemd/user")
+            .join(format!("{NAME}.socket"))
+    }
+
+    /// The icon used by the desktop entries.
+    fn icon(&self) -> PathBuf {
+        self.data
+            .join("icons/hicolor")
+            .join(ICON_SIZE)
+            .join("apps")
+            .join(format!("{NAME}.png"))
+    }
+}
+
+/// Installs the autostart entry, launcher & icon, returning the files that were written.
+///
+/// The autostart entry starts the tray without opening the GUI.
+pub fn install(dirs: &Dirs, exe: &Path) -> Result<Vec<PathBuf>, InstallError> {
+    let files = [
+        (
+            dirs.autostart(),
+            desktop_entry(exe, &["--no-gui"], true).into_bytes(),
+        ),
+        (dirs.launcher(), desktop_entry(exe, &[], false).into_bytes()),
+        (dirs.icon(), ICON.to_vec()),
+    ];
+
+    files
+        .into_iter()
+        .map(|(path, contents)| write(path, contents))
+        .collect()
+}
+
+/// Installs a systemd user service & socket that start the tray without an icon when a GUI connects, returning the
+/// files that were written.
+pub fn install_units(
+    dirs: &Dirs,
+    exe: &Path,
+    transport: TransportKind,
+) -> Result<Vec<PathBuf>, InstallError> {
+    let files = [
+        (dirs.service(), systemd::service_unit(exe, transport)),
+        (dirs.socket(), systemd::socket_unit(transport)),
+    ];
+
+    files
+        .into_iter()
+        .map(|(path, contents)| write(path, contents.into_bytes()))
+        .collect()
+}
+
+/// Writes the file, creating the directory it is in.
+fn write(path: PathBuf, contents: Vec<u8>) -> Result<PathBuf, InstallError> {
+    let write = || {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&path, contents)
+    };
+
+    match write() {
+        Ok(()) => Ok(path),
+        Err(source) => Err(InstallError::Write { path, source }),
+    }
+}
+
+/// Removes everything written by [`install`] & [`install_units`], returning the files that were removed.
+pub fn uninstall(dirs: &Dirs) -> Result<Vec<PathBuf>, InstallError> {
+    let mut removed = Vec::new();
+
+    for path in [
+        dirs.autostart(),
+        dirs.launcher(),
+        dirs.icon(),
+        dirs.service(),
+        dirs.socket(),
+    ] {
+        match std::fs::remove_file(&path) {
+            Ok(()) => removed.push(path),
+            Err(err) if err.kind() == ErrorKind::NotFound => {}
+            Err(source) => return Err(InstallError::Remove { path, source }),
+        }
+    }
+
+    Ok(removed)
+}
+
+/// A desktop entry that runs the executable with the given arguments.
+fn desktop_entry(exe: &Path, args: &[&str], autostart: bool) -> String {
+    let exec = std::iter::once(exe.to_string_lossy().as_ref())
+        .chain(args.iter().copied())
+        .map(quote)
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let mut entry = format!(
+        "[Desktop Entry]
+Type=Application
+Name=Gui Timer
+Comment=Timers that live in the system tray
+Exec={exec}
+Icon={NAME}
+Terminal=false
+Categories=Utility;Clock;
+"
+    );
+
+    if autostart {
+        entry.push_str("X-GNOME-Autostart-enabled=true\n");
+    }
+
+    entry
+}
+
+/// Quotes an argument in the `Exec` key of a desktop entry, if it needs to be.
+fn quote(arg: &str) -> String {
+    const RESERVED: &[char] = &[
+        ' ', '\t', '\n', '"', '\'', '\\', '>', '<', '~', '|', '&', ';', '$', '*', '?', '#', '(',
+        ')', '`',
+    ];
+
+    if !arg.contains(RESERVED) {
+        // A literal percent sign is escaped as a field code.
+        return arg.replace('%', "%%");
+    }
+
+    let mut quoted = String::from('"');
+    for char in arg.chars() {
+        if matches!(char, '"' | '`' | '$' | '\\') {
+            quoted.push('\\');
+        }
+        quoted.push(char);
+    }
+    quoted.push('"');
+    // Desktop entry values escape backslashes again when they are read.
+    quoted.replace('\\', "\\\\").replace('%', "%%")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{Dirs, desktop_entry, install, quote, uninstall};
+
+    #[test]
+    fn install_uninstall() {
+        let dir = tempfile::tempdir().expect("Can create temp dir");
+        let dirs = Dirs {
+            config: dir.path().join("config"),
+            data: dir.path().join("data"),
+        };
+
+        let installed = install(&dirs, Path::new("/usr/bin/gui_timer")).expect("Can install");
+        assert_eq!(installed.len(), 3);
+        assert!(installed.iter().all(|path| path.is_file()));
+
+        let autostart = std::fs::read_to_string(&installed[0]).expect("Can read");
+        assert!(autostart.contains("Exec=/usr/bin/gui_timer --no-gui\n"));
+
+        assert_eq!(uninstall(&dirs).expect("Can uninstall"), installed);
+        assert!(installed.iter().all(|path| !path.exists()));
+        assert!(uninstall(&dirs).expect("Can uninstall").is_empty());
+    }
+
+    #[test]
+    fn quoting() {
+        assert_eq!(quote("/usr/bin/gui_timer"), "/usr/bin/gui_timer");
+        assert_eq!(quote("50%"), "50%%");
+        assert_eq!(quote("/opt/my timer"), r#""/opt/my timer""#);
+        assert_eq!(quote(r#"a"b$c"#), r#""a\\"b\\$c""#);
+
+        let entry = desktop_entry(Path::new("/opt/my timer"), &[], false);
+        assert!(entry.contains("Exec=\"/opt/my timer\"\n"));
+        assert!(!entry.contains("Autostart"));
+    }
+}
@@ -1,26 +1,152 @@
+use std::{path::PathBuf, process::ExitCode};
+
 use clap::Parser;
-use gui::launch_gui;
-use tray::launch_tray;
+use comms::{
+    TransportKind,
+    capture::{self, Endpoint},
+};
+use gui::{launch_gui, launch_prompt};
+use tray::{TrayOptions, launch_tray};
 
+mod clock;
 mod comms;
+mod dnd;
+mod format;
 mod gui;
+mod install;
+mod shortcuts;
+mod systemd;
+mod timer;
 mod tray;
 
-fn main() {
+fn main() -> ExitCode {
     env_logger::init();
 
     let args = Args::parse();
 
+    if let Some(command) = args.command {
+        return match run_command(command, args.transport) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(err) => {
+                eprintln!("{err}");
+                ExitCode::FAILURE
+            }
+        };
+    }
+
+    if let Some(path) = args.replay {
+        return match comms::replay::replay(&path, args.replay_into, args.transport) {
+            Ok(()) => ExitCode::SUCCESS,
+            Err(err) => {
+                log::error!("Unable to replay '{}': {err}", path.display());
+                ExitCode::FAILURE
+            }
+        };
+    }
+
+    if let Some(path) = args.capture {
+        let endpoint = match args.gui {
+            true => Endpoint::Gui,
+            false => Endpoint::Tray,
+        };
+
+        capture::start(&path, endpoint)
+            .unwrap_or_else(|err| panic!("Unable to capture to '{}': {err}", path.display()));
+    }
+
+    if args.prompt {
+        launch_prompt();
+        return ExitCode::SUCCESS;
+    }
+
     match args.gui {
-        true => launch_gui(),
-        false => launch_tray(),
+        true => launch_gui(args.transport),
+        false => launch_tray(TrayOptions {
+            transport: args.transport,
+            icons: args.icons,
+            restart_gui: args.restart_gui,
+            daemon: args.daemon,
+            open_gui: !args.no_gui,
+            // SAFETY: No other threads have been spawned yet.
+            listeners: unsafe { systemd::take_listen_fds() },
+        }),
     }
+    ExitCode::SUCCESS
+}
+
+/// Runs a subcommand instead of the tray or GUI, printing the paths it changed.
+fn run_command(command: Command, transport: TransportKind) -> Result<(), install::InstallError> {
+    let dirs = install::Dirs::from_env()?;
+
+    let paths = match command {
+        Command::Install => std::env::current_exe()
+            .map_err(install::InstallError::Exe)
+            .and_then(|exe| install::install(&dirs, &exe)),
+        Command::Systemd => std::env::current_exe()
+            .map_err(install::InstallError::Exe)
+            .and_then(|exe| install::install_units(&dirs, &exe, transport)),
+        Command::Uninstall => install::uninstall(&dirs),
+    }?;
+
+    paths.iter().for_each(|path| println!("{}", path.display()));
+    Ok(())
 }
 
 /// Args parsed from CLI.
 #[derive(clap::Parser)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
     /// Whether to launch the GUI instead of the tray.
     #[arg(long)]
     gui: bool,
+    /// Asks for the length of a new timer & prints it in seconds, as used by the tray.
+    #[arg(long, hide = true, conflicts_with = "gui")]
+    prompt: bool,
+    /// Runs the timers without a tray icon, for desktops without a system tray or remote sessions.
+    ///
+    /// GUIs can still be opened with '--gui'. This is also done when the tray icon cannot be shown.
+    #[arg(long, conflicts_with = "gui")]
+    daemon: bool,
+    /// Starts the tray without opening the GUI.
+    #[arg(long, conflicts_with = "gui")]
+    no_gui: bool,
+    /// How the GUI connects to the tray.
+    #[arg(long, value_enum, default_value_t)]
+    transport: TransportKind,
+    /// A directory containing the tray icons to use instead of the default icon.
+    ///
+    /// Icons are loaded from 'idle.png', 'running.png', 'paused.png' & 'finished.png'.
+    #[arg(long, value_name = "DIR")]
+    icons: Option<PathBuf>,
+    /// Opens the GUI again if it crashes whilst open.
+    #[arg(long, conflicts_with = "gui")]
+    restart_gui: bool,
+    /// Records all communication between the tray & the GUI to the given file.
+    #[arg(long, value_name = "FILE")]
+    capture: Option<PathBuf>,
+    /// Feeds the communication recorded in the given capture file into a running tray or GUI.
+    ///
+    /// This connects with the transport given with '--transport'.
+    #[arg(long, value_name = "FILE", conflicts_with = "capture")]
+    replay: Option<PathBuf>,
+    /// What to feed the replayed capture into.
+    #[arg(long, value_enum, default_value_t = Endpoint::Tray, requires = "replay")]
+    replay_into: Endpoint,
+}
+
+/// Subcommands that are run instead of the tray or GUI.
+#[derive(clap::Subcommand)]
+enum Command {
+    /// Starts the tray when logging in & adds it to the application launcher, for the current user.
+    ///
+    /// This respects the XDG_CONFIG_HOME & XDG_DATA_HOME environment variables.
+    Install,
+    /// Adds a systemd user service & socket that start the tray without an icon when a GUI connects to it.
+    ///
+    /// The socket listens for the transport given with '--transport', using 'unix' for 'pipe'. Enable it with
+    /// 'systemctl --user enable --now gui_timer.socket'.
+    Systemd,
+    /// Removes everything added by 'install' & 'systemd'.
+    Uninstall,
 }
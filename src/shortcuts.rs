@@ -0,0 +1,105 @@
+//! System-wide keyboard shortcuts, which are bound by the desktop through the XDG GlobalShortcuts portal.
+
+/// An action that can be performed with a keyboard shortcut.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Shortcut {
+    /// Starts a timer from the first preset.
+    StartTimer,
+    /// Pauses every timer if any are running, otherwise resumes them all.
+    PauseAll,
+    /// Opens or closes the GUI.
+    ToggleGui,
+}
+
+impl Shortcut {
+    pub const ALL: [Shortcut; 3] = [
+        Shortcut::StartTimer,
+        Shortcut::PauseAll,
+        Shortcut::ToggleGui,
+    ];
+
+    /// The id the shortcut is bound with.
+    pub fn id(self) -> &'static str {
+        match self {
+            Shortcut::StartTimer => "start-timer",
+            Shortcut::PauseAll => "pause-all",
+            Shortcut::ToggleGui => "toggle-gui",
+        }
+    }
+
+    /// The shortcut bound with the id, if there is one.
+    pub fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|shortcut| shortcut.id() == id)
+    }
+
+    /// What the shortcut does, as shown to the user.
+    pub fn description(self) -> &'static str {
+        match self {
+            Shortcut::StartTimer => "Start a timer",
+            Shortcut::PauseAll => "Pause or resume all timers",
+            Shortcut::ToggleGui => "Open or close the timers window",
+        }
+    }
+}
+
+/// The keys the user would like to trigger each shortcut, in the format of the XDG shortcuts specification, such as
+/// `CTRL+ALT+t`.
+///
+/// The desktop has the final say over which keys are bound.
+#[derive(
+    bincode::Decode, bincode::Encode, serde::Serialize, serde::Deserialize, Clone, PartialEq, Debug,
+)]
+#[serde(default)]
+pub struct Shortcuts {
+    pub start_timer: String,
+    pub pause_all: String,
+    pub toggle_gui: String,
+}
+
+impl Shortcuts {
+    /// The keys the user would like to trigger the shortcut.
+    pub fn trigger(&self, shortcut: Shortcut) -> &str {
+        match shortcut {
+            Shortcut::StartTimer => &self.start_timer,
+            Shortcut::PauseAll => &self.pause_all,
+            Shortcut::ToggleGui => &self.toggle_gui,
+        }
+    }
+
+    /// The keys the user would like to trigger the shortcut, to be edited.
+    pub fn trigger_mut(&mut self, shortcut: Shortcut) -> &mut String {
+        match shortcut {
+            Shortcut::StartTimer => &mut self.start_timer,
+            Shortcut::PauseAll => &mut self.pause_all,
+            Shortcut::ToggleGui => &mut self.toggle_gui,
+        }
+    }
+}
+
+impl Default for Shortcuts {
+    fn default() -> Self {
+        Self {
+            start_timer: "CTRL+ALT+t".into(),
+            pause_all: "CTRL+ALT+p".into(),
+            toggle_gui: "CTRL+ALT+g".into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Shortcut, Shortcuts};
+
+    #[test]
+    fn ids() {
+        for shortcut in Shortcut::ALL {
+            assert_eq!(Shortcut::from_id(shortcut.id()), Some(shortcut));
+        }
+        assert_eq!(Shortcut::from_id("quit"), None);
+
+        let mut shortcuts = Shortcuts::default();
+        *shortcuts.trigger_mut(Shortcut::PauseAll) = "CTRL+space".into();
+        assert_eq!(shortcuts.pause_all, "CTRL+space");
+        assert_eq!(shortcuts.trigger(Shortcut::PauseAll), "CTRL+space");
+    }
+}
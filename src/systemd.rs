@@ -0,0 +1,210 @@
+//! Running the tray as a systemd user service, with socket activation & readiness notification.
+
+use std::{
+    ffi::OsStr,
+    os::{
+        fd::{FromRawFd, OwnedFd, RawFd},
+        linux::net::SocketAddrExt,
+        unix::{ffi::OsStrExt, net::UnixDatagram},
+    },
+    path::Path,
+};
+
+use crate::comms::{SOCKET_ADDR, TransportKind};
+
+/// The first file descriptor passed in by systemd.
+const LISTEN_FDS_START: RawFd = 3;
+
+/// Takes the listening sockets passed in by systemd when the tray is socket activated.
+///
+/// The variables describing the sockets are removed from the environment, so they are not inherited by the GUI.
+///
+/// # Safety
+/// This modifies the environment, so must be called before any other threads are spawned.
+pub unsafe fn take_listen_fds() -> Vec<OwnedFd> {
+    let count = listen_fd_count(
+        std::env::var("LISTEN_PID").ok().as_deref(),
+        std::env::var("LISTEN_FDS").ok().as_deref(),
+        std::process::id(),
+    );
+
+    // SAFETY: The caller ensures that no other threads are running.
+    unsafe {
+        std::env::remove_var("LISTEN_PID");
+        std::env::remove_var("LISTEN_FDS");
+        std::env::remove_var("LISTEN_FDNAMES");
+    }
+
+    (LISTEN_FDS_START..LISTEN_FDS_START + count as RawFd)
+        .map(|fd| {
+            // Keep the sockets from being inherited by the GUI.
+            // SAFETY: systemd passed ownership of these file descriptors to this process.
+            unsafe {
+                libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC);
+                OwnedFd::from_raw_fd(fd)
+            }
+        })
+        .collect()
+}
+
+/// The number of sockets passed in by systemd, given the values of `LISTEN_PID` & `LISTEN_FDS`.
+///
+/// The sockets are only for this process if `LISTEN_PID` is its own pid.
+fn listen_fd_count(listen_pid: Option<&str>, listen_fds: Option<&str>, pid: u32) -> usize {
+    if listen_pid.and_then(|listen_pid| listen_pid.parse().ok()) != Some(pid) {
+        return 0;
+    }
+
+    listen_fds
+        .and_then(|listen_fds| listen_fds.parse().ok())
+        .unwrap_or(0)
+}
+
+/// Tells systemd about a change in the state of the tray, such as `READY=1`.
+///
+/// Nothing is sent if the tray was not started by systemd.
+pub fn notify(state: &str) -> std::io::Result<()> {
+    match std::env::var_os("NOTIFY_SOCKET") {
+        Some(socket) => notify_to(&socket, state),
+        None => Ok(()),
+    }
+}
+
+/// Sends the state to the given notification socket, which may be in the abstract namespace.
+fn notify_to(socket: &OsStr, state: &str) -> std::io::Result<()> {
+    let addr = match socket.as_bytes().strip_prefix(b"@") {
+        Some(name) => std::os::unix::net::SocketAddr::from_abstract_name(name)?,
+        None => std::os::unix::net::SocketAddr::from_pathname(socket)?,
+    };
+
+    UnixDatagram::unbound()?.send_to_addr(state.as_bytes(), &addr)?;
+    Ok(())
+}
+
+/// The user service that runs the tray without an icon, started when a GUI connects to its socket.
+pub fn service_unit(exe: &Path, transport: TransportKind) -> String {
+    format!(
+        "[Unit]
+Description=Gui Timer
+Requires={name}.socket
+After={name}.socket
+
+[Service]
+Type=notify
+ExecStart={exe} --daemon --transport {transport}
+",
+        name = env!("CARGO_PKG_NAME"),
+        exe = quote(&exe.to_string_lossy()),
+        transport = match transport {
+            TransportKind::Tcp => "tcp",
+            TransportKind::Unix | TransportKind::Pipe => "unix",
+        },
+    )
+}
+
+/// The socket that starts the user service when a GUI connects to it.
+pub fn socket_unit(transport: TransportKind) -> String {
+    let listen = match transport {
+        TransportKind::Tcp => SOCKET_ADDR.to_string(),
+        // The same path as `socket_path`, as `%t` is the user's runtime directory.
+        TransportKind::Unix | TransportKind::Pipe => {
+            concat!("%t/", env!("CARGO_PKG_NAME"), ".sock").into()
+        }
+    };
+
+    format!(
+        "[Unit]
+Description=Gui Timer socket
+
+[Socket]
+ListenStream={listen}
+
+[Install]
+WantedBy=sockets.target
+"
+    )
+}
+
+/// Quotes an argument in a unit file, if it needs to be.
+fn quote(arg: &str) -> String {
+    // A literal percent sign is escaped as a specifier.
+    let arg = arg.replace('%', "%%");
+    if !arg.contains([' ', '\t', '"', '\'', '\\', ';']) {
+        return arg;
+    }
+
+    format!("\"{}\"", arg.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read as _, Write as _},
+        os::{fd::OwnedFd, unix::net::UnixDatagram},
+        path::Path,
+    };
+
+    use tokio::io::{AsyncReadExt as _, AsyncWriteExt as _};
+
+    use super::{listen_fd_count, notify_to, quote, service_unit, socket_unit};
+    use crate::comms::{
+        TransportKind,
+        transport::{Listener, TcpTransport, Transport as _},
+    };
+
+    #[test]
+    fn listen_fds_for_this_process() {
+        assert_eq!(listen_fd_count(Some("42"), Some("2"), 42), 2);
+        assert_eq!(listen_fd_count(Some("41"), Some("2"), 42), 0);
+        assert_eq!(listen_fd_count(None, Some("2"), 42), 0);
+        assert_eq!(listen_fd_count(Some("42"), Some("x"), 42), 0);
+    }
+
+    #[tokio::test]
+    async fn activated_listener() {
+        // A socket passed in the way systemd would.
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("Can bind");
+        let addr = listener.local_addr().expect("Has address");
+        let fd = OwnedFd::from(listener);
+
+        let transport = TcpTransport::new(addr).with_listener(std::net::TcpListener::from(fd));
+        let mut listener = transport.listen().await.expect("Can listen");
+
+        let mut client = std::net::TcpStream::connect(addr).expect("Can connect");
+        let mut server = Listener::accept(&mut listener).await.expect("Can accept");
+
+        client.write_all(b"hi").expect("Can write");
+        let mut buf = [0; 2];
+        server.read_exact(&mut buf).await.expect("Can read");
+        assert_eq!(&buf, b"hi");
+
+        server.write_all(b"ok").await.expect("Can write");
+        client.read_exact(&mut buf).expect("Can read");
+        assert_eq!(&buf, b"ok");
+    }
+
+    #[test]
+    fn notify() {
+        let dir = tempfile::tempdir().expect("Can create temp dir");
+        let path = dir.path().join("notify");
+        let socket = UnixDatagram::bind(&path).expect("Can bind");
+
+        notify_to(path.as_os_str(), "READY=1").expect("Can notify");
+
+        let mut buf = [0; 16];
+        let len = socket.recv(&mut buf).expect("Can receive");
+        assert_eq!(&buf[..len], b"READY=1");
+    }
+
+    #[test]
+    fn units() {
+        let service = service_unit(Path::new("/opt/gui timer"), TransportKind::Unix);
+        assert!(service.contains("ExecStart=\"/opt/gui timer\" --daemon --transport unix\n"));
+        assert!(service.contains("Type=notify\n"));
+
+        assert!(socket_unit(TransportKind::Tcp).contains("ListenStream=127.0.0.1:23408\n"));
+        assert!(socket_unit(TransportKind::Unix).contains("ListenStream=%t/gui_timer.sock\n"));
+
+        assert_eq!(quote("50%"), "50%%");
+    }
+}
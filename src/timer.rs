@@ -0,0 +1,461 @@
+use std::{
+    ops::Add,
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::format::TimeFormat;
+
+/// The state of a single timer.
+///
+/// The tray holds the authoritative state of every timer, with clients keeping copies that are kept in sync with
+/// [`timer_sync`](crate::comms::timer_sync).
+///
+/// This data can also be seralised and deserialised.
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq, Debug)]
+pub struct TimerData {
+    /// When the timer was last updated.
+    /// If this is None, then this is the first update.
+    #[serde(skip)]
+    last_ticked: Option<Instant>,
+    /// The time on the wall clock when the timer was last updated.
+    /// This is saved, so a running timer counts the time it was not loaded for.
+    #[serde(default)]
+    ticked_at: Option<SystemTime>,
+    /// How much time has passed.
+    duration: Duration,
+    /// After how long will the timer end.
+    end_after: Duration,
+    /// Whether the timer is running.
+    paused: bool,
+    /// Whether the user has seen that the timer finished.
+    #[serde(default)]
+    acknowledged: bool,
+    /// Whether the timer is paused whilst the user is away from their computer.
+    #[serde(default)]
+    pause_when_away: bool,
+    /// How much of the time that has passed the user was away from their computer for.
+    #[serde(default)]
+    away: Duration,
+    /// When the timer stops the computer from sleeping.
+    #[serde(default)]
+    keep_awake: KeepAwake,
+    /// How the timer is told apart from the others.
+    #[serde(default)]
+    appearance: Appearance,
+    /// How the timer's times are shown, instead of the format chosen for every timer.
+    #[serde(default)]
+    time_format: Option<TimeFormat>,
+    /// Whether the timer keeps counting after it ends, until the user acknowledges it.
+    #[serde(default)]
+    overtime: bool,
+    /// How long the timer counted past its end before it was restarted, extended or overtime was turned off.
+    #[serde(default)]
+    earlier_overrun: Duration,
+    /// How much of the timer's current overrun the user chose to forget, so it is not recorded.
+    #[serde(default)]
+    forgotten_overrun: Duration,
+}
+
+impl TimerData {
+    /// Create new [`TimerData`] with the given timer duration.
+    pub fn new(end_after: Duration) -> Self {
+        Self {
+            last_ticked: Some(Instant::now()),
+            ticked_at: Some(SystemTime::now()),
+            duration: Duration::ZERO,
+            end_after,
+            paused: false,
+            acknowledged: false,
+            pause_when_away: false,
+            away: Duration::ZERO,
+            keep_awake: KeepAwake::Never,
+            appearance: Appearance::default(),
+            time_format: None,
+            overtime: false,
+            earlier_overrun: Duration::ZERO,
+            forgotten_overrun: Duration::ZERO,
+        }
+    }
+
+    /// Increments the time that has passed by the time since the last tick.
+    pub fn tick(&mut self) {
+        if self.paused {
+            return;
+        }
+
+        let now = Instant::now();
+        let elapsed = match (self.last_ticked, self.ticked_at) {
+            (Some(last_tick), _) => Some(now - last_tick),
+            // The timer was loaded, so the time since it was saved has passed too.
+            (None, Some(ticked_at)) => SystemTime::now().duration_since(ticked_at).ok(),
+            (None, None) => None,
+        };
+        if let Some(elapsed) = elapsed {
+            let duration = self.duration.add(elapsed);
+            self.duration = match self.overtime && !self.acknowledged {
+                true => duration,
+                // Any overrun already counted is kept.
+                false => duration.min(self.end_after).max(self.duration),
+            };
+        }
+
+        self.mark_ticked();
+    }
+
+    /// Records that the timer was updated just now.
+    fn mark_ticked(&mut self) {
+        self.last_ticked = Some(Instant::now());
+        self.ticked_at = Some(SystemTime::now());
+    }
+
+    /// Whether a [`TimerData`] is puased.
+    pub fn pause(&mut self, pause: bool) {
+        self.tick();
+        self.paused = pause;
+        self.mark_ticked();
+    }
+
+    /// Sets the amount of time that has passed to 0.
+    pub fn reset(&mut self) {
+        self.keep_overrun(|timer| timer.duration = Duration::ZERO);
+        self.away = Duration::ZERO;
+        self.mark_ticked();
+        self.acknowledged = false;
+    }
+
+    /// Extends the timer by the given amount of time.
+    pub fn add_time(&mut self, extra: Duration) {
+        self.keep_overrun(|timer| timer.end_after += extra);
+        self.acknowledged = false;
+    }
+
+    /// Shortens the timer by the given amount of time, ending it if less than that is left.
+    ///
+    /// Only the time the timer had counted past its old end is recorded as overrun, not the time that was removed.
+    pub fn remove_time(&mut self, less: Duration) {
+        self.keep_overrun(|timer| {
+            timer.end_after = timer.end_after.saturating_sub(less);
+            timer.duration = timer.duration.min(timer.end_after);
+        });
+    }
+
+    /// Sets whether the timer keeps counting after it ends, until the user acknowledges it.
+    ///
+    /// Turning overtime off stops showing any overrun, though it is still recorded.
+    pub fn set_overtime(&mut self, overtime: bool) {
+        self.overtime = overtime;
+        if !overtime {
+            self.keep_overrun(|timer| timer.duration = timer.duration.min(timer.end_after));
+        }
+    }
+
+    /// Records any overrun the given change discards.
+    fn keep_overrun(&mut self, change: impl FnOnce(&mut Self)) {
+        let overrun = self.current_overrun();
+        change(self);
+        self.forgotten_overrun = self.forgotten_overrun.min(self.overrun());
+        self.earlier_overrun += overrun.saturating_sub(self.current_overrun());
+    }
+
+    /// Forgets how long the timer has counted past its end, including before it was restarted.
+    pub fn forget_overrun(&mut self) {
+        self.earlier_overrun = Duration::ZERO;
+        self.forgotten_overrun = self.overrun();
+    }
+
+    /// Sets whether the timer is paused whilst the user is away from their computer.
+    pub fn set_pause_when_away(&mut self, pause_when_away: bool) {
+        self.pause_when_away = pause_when_away;
+    }
+
+    /// Sets when the timer stops the computer from sleeping.
+    pub fn set_keep_awake(&mut self, keep_awake: KeepAwake) {
+        self.keep_awake = keep_awake;
+    }
+
+    /// Sets how the timer is told apart from the others.
+    pub fn set_appearance(&mut self, appearance: Appearance) {
+        self.appearance = appearance;
+    }
+
+    /// Sets how the timer's times are shown, or uses the format chosen for every timer if there is none.
+    pub fn set_time_format(&mut self, time_format: Option<TimeFormat>) {
+        self.time_format = time_format;
+    }
+
+    /// Records that the user was away for some of the time that has passed.
+    pub fn record_away(&mut self, away: Duration) {
+        self.away = (self.away + away).min(self.duration);
+    }
+
+    /// Marks that the user has seen that the timer finished.
+    pub fn acknowledge(&mut self) {
+        self.acknowledged = true;
+    }
+
+    /// How much time has passed.
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    /// After how long the timer will end.
+    pub fn end_after(&self) -> Duration {
+        self.end_after
+    }
+
+    /// How much time is left until the timer ends.
+    pub fn remaining(&self) -> Duration {
+        self.end_after.saturating_sub(self.duration)
+    }
+
+    /// Whether the timer is paused.
+    pub fn paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Whether the timer is paused whilst the user is away from their computer.
+    pub fn pause_when_away(&self) -> bool {
+        self.pause_when_away
+    }
+
+    /// How much of the time that has passed the user was away from their computer for.
+    pub fn away(&self) -> Duration {
+        self.away
+    }
+
+    /// When the timer stops the computer from sleeping.
+    pub fn keep_awake(&self) -> KeepAwake {
+        self.keep_awake
+    }
+
+    /// How the timer is told apart from the others.
+    pub fn appearance(&self) -> &Appearance {
+        &self.appearance
+    }
+
+    /// How the timer's times are shown, if it differs from the format chosen for every timer.
+    pub fn time_format(&self) -> Option<TimeFormat> {
+        self.time_format
+    }
+
+    /// Whether the timer keeps counting after it ends, until the user acknowledges it.
+    pub fn overtime(&self) -> bool {
+        self.overtime
+    }
+
+    /// How long the timer has counted past its end.
+    pub fn overrun(&self) -> Duration {
+        self.duration.saturating_sub(self.end_after)
+    }
+
+    /// How long the timer has counted past its end in total, including before it was restarted.
+    pub fn recorded_overrun(&self) -> Duration {
+        self.earlier_overrun + self.current_overrun()
+    }
+
+    /// How much of the overrun the timer is counting now is recorded.
+    fn current_overrun(&self) -> Duration {
+        self.overrun().saturating_sub(self.forgotten_overrun)
+    }
+
+    /// Whether the timer needs the computer to stay awake right now, so it is not missed when it ends.
+    pub fn keeps_awake(&self) -> bool {
+        if self.paused || self.finished() {
+            return false;
+        }
+
+        match self.keep_awake {
+            KeepAwake::Never => false,
+            KeepAwake::WhileRunning => true,
+            KeepAwake::Final(window) => self.remaining() <= window,
+        }
+    }
+
+    /// Whether the timer has ended.
+    pub fn finished(&self) -> bool {
+        self.duration >= self.end_after
+    }
+
+    /// Whether the timer has finished without the user acknowledging it.
+    pub fn needs_attention(&self) -> bool {
+        self.finished() && !self.acknowledged
+    }
+
+    /// The fraction of the timer that has passed, between 0 & 1.
+    pub fn progress(&self) -> f32 {
+        if self.end_after.is_zero() {
+            return 1.0;
+        }
+        self.duration.div_duration_f32(self.end_after).min(1.0)
+    }
+}
+
+/// When a timer stops the computer from sleeping.
+#[derive(
+    bincode::Decode,
+    bincode::Encode,
+    serde::Serialize,
+    serde::Deserialize,
+    Clone,
+    Copy,
+    PartialEq,
+    Debug,
+    Default,
+)]
+pub enum KeepAwake {
+    /// The computer may sleep whilst the timer runs.
+    #[default]
+    Never,
+    /// The computer is kept awake for as long as the timer runs.
+    WhileRunning,
+    /// The computer is kept awake once the timer is within this long of ending.
+    Final(Duration),
+}
+
+/// How a timer is told apart from the others.
+#[derive(
+    bincode::Decode,
+    bincode::Encode,
+    serde::Serialize,
+    serde::Deserialize,
+    Clone,
+    PartialEq,
+    Debug,
+    Default,
+)]
+#[serde(default)]
+pub struct Appearance {
+    /// The name the user gave the timer, which may be empty.
+    pub label: String,
+    /// The colour of the timer's progress.
+    pub colour: Colour,
+    /// An emoji shown alongside the timer, which may be empty.
+    pub emoji: String,
+}
+
+impl Appearance {
+    /// The name the timer is shown with, falling back to the given name if the user has not named it.
+    pub fn name(&self, fallback: impl FnOnce() -> String) -> String {
+        let label = match self.label.trim() {
+            "" => fallback(),
+            label => label.to_string(),
+        };
+
+        match self.emoji.trim() {
+            "" => label,
+            emoji => format!("{emoji} {label}"),
+        }
+    }
+}
+
+/// The colours a timer can be shown in.
+#[derive(
+    bincode::Decode,
+    bincode::Encode,
+    serde::Serialize,
+    serde::Deserialize,
+    Clone,
+    Copy,
+    PartialEq,
+    Debug,
+    Default,
+)]
+pub enum Colour {
+    #[default]
+    Blue,
+    Green,
+    Yellow,
+    Orange,
+    Red,
+    Pink,
+    Purple,
+    Grey,
+}
+
+impl Colour {
+    /// Every colour, in the order they are offered to the user.
+    pub const PALETTE: [Colour; 8] = [
+        Colour::Blue,
+        Colour::Green,
+        Colour::Yellow,
+        Colour::Orange,
+        Colour::Red,
+        Colour::Pink,
+        Colour::Purple,
+        Colour::Grey,
+    ];
+
+    /// The red, green & blue components of the colour.
+    pub fn rgb(self) -> [u8; 3] {
+        match self {
+            Colour::Blue => [173, 216, 230],
+            Colour::Green => [130, 200, 120],
+            Colour::Yellow => [240, 210, 80],
+            Colour::Orange => [245, 160, 70],
+            Colour::Red => [230, 95, 90],
+            Colour::Pink => [240, 140, 190],
+            Colour::Purple => [170, 130, 220],
+            Colour::Grey => [160, 160, 160],
+        }
+    }
+}
+
+/// A timer that can be started quickly from the tray.
+#[derive(
+    bincode::Decode, bincode::Encode, serde::Serialize, serde::Deserialize, Clone, PartialEq, Debug,
+)]
+pub struct Preset {
+    /// The name shown for the preset.
+    pub name: String,
+    /// After how long timers started from the preset will end.
+    pub end_after: Duration,
+}
+
+impl Preset {
+    /// The presets available before the user has changed them.
+    pub fn defaults() -> Vec<Self> {
+        [("Tea", 4), ("Standup", 15), ("Focus", 25)]
+            .into_iter()
+            .map(|(name, minutes)| Self {
+                name: name.into(),
+                end_after: Duration::from_secs(minutes * 60),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::TimerData;
+
+    #[test]
+    fn restoring_running_timers() {
+        let mut running = TimerData::new(Duration::from_secs(60));
+        let mut paused = TimerData::new(Duration::from_secs(60));
+        paused.pause(true);
+        let counted = paused.duration();
+
+        // The timers were saved half a minute before they are loaded again.
+        let half_minute = Duration::from_secs(30);
+        let saved = [&mut running, &mut paused].map(|timer| {
+            timer.ticked_at = timer.ticked_at.map(|ticked_at| ticked_at - half_minute);
+            ron::to_string(timer).expect("Timer can be saved")
+        });
+        let [mut running, mut paused] =
+            saved.map(|saved| ron::from_str::<TimerData>(&saved).expect("Timer can be loaded"));
+        assert_eq!(running.last_ticked, None);
+
+        running.tick();
+        assert!(running.duration() >= half_minute);
+        assert!(running.duration() < Duration::from_secs(60));
+
+        // Paused timers count nothing whilst they were saved.
+        paused.tick();
+        assert_eq!(paused.duration(), counted);
+        paused.pause(false);
+        paused.tick();
+        assert!(paused.duration() < half_minute);
+    }
+}
@@ -1,55 +1,81 @@
 use crate::comms::async_socket::{AsyncReadObj, AsyncWriteObj};
-use crate::comms::{GuiAction, GuiResponse, SOCKET_ADDR};
+use crate::comms::transport::{Listener as _, Transport};
+use crate::comms::{GuiAction, GuiResponse};
 use crate::tray::GLOBAL_CANCEL;
 use crate::until_global_cancel;
-use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
-use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::{
+    broadcast::{self, error::RecvError},
+    mpsc::UnboundedSender,
+};
 use tokio_util::sync::CancellationToken;
 
-/// Starts communication between the gui & the tray.
+/// How many actions a GUI can fall behind the tray by before it misses some.
+pub(crate) const BACKLOG: usize = 256;
+
+/// Identifies a GUI connected to the tray, amongst every connection made to it.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub(crate) struct ClientId(pub u64);
+
+/// An action sent by the tray, along with the GUI it is for.
+///
+/// Every action is sent through the same channel, so each GUI receives them in the order the tray sent them.
+#[derive(Clone, Debug)]
+pub(crate) struct Outgoing {
+    /// The only GUI that is sent the action, otherwise it is sent to every GUI.
+    pub to: Option<ClientId>,
+    pub action: GuiAction,
+}
+
+/// Starts communication between the GUIs & the tray.
 ///
-/// This method should only be called once, as when a new tray will be connected to when it opens.
-pub(crate) async fn init_communication(
-    mut sender: UnboundedSender<GuiResponse>,
-    mut receiver: UnboundedReceiver<GuiAction>,
+/// Every GUI that connects is served at the same time. Actions sent by the tray are sent to each of them, & their
+/// responses are sent to the tray along with the [`ClientId`] they came from.
+pub(crate) async fn init_communication<T: Transport>(
+    sender: UnboundedSender<(ClientId, GuiResponse)>,
+    actions: broadcast::Sender<Outgoing>,
+    transport: T,
 ) {
-    let listener = match tokio::net::TcpListener::bind(SOCKET_ADDR).await {
+    let mut listener = match transport.listen().await {
         Ok(listener) => listener,
         Err(err) => {
-            log::error!("Unable to connect listen for gui on {SOCKET_ADDR}: {err}");
+            log::error!("Unable to listen for gui: {err}");
             GLOBAL_CANCEL.cancel();
             return;
         }
     };
 
-    while !GLOBAL_CANCEL.is_cancelled() {
-        until_global_cancel!(async {
-            let (stream, _) = match listener.accept().await {
-                Ok(val) => val,
-                Err(err) => {
-                    log::error!(
-                        "An error occurred whilst listening for gui on {SOCKET_ADDR}: {err}"
-                    );
-                    GLOBAL_CANCEL.cancel();
-                    return;
-                }
-            };
+    for id in 0.. {
+        let stream = match until_global_cancel!(listener.accept()) {
+            Ok(val) => val,
+            Err(err) => {
+                log::error!("An error occurred whilst listening for gui: {err}");
+                GLOBAL_CANCEL.cancel();
+                return;
+            }
+        };
 
-            let (rx, tx) = stream.into_split();
-            let close = GLOBAL_CANCEL.child_token();
+        let (rx, tx) = T::split(stream);
+        let client = ClientId(id);
+        // Subscribed before the GUI can send anything, so it misses nothing the tray sends in response.
+        let actions = actions.subscribe();
+        let sender = sender.clone();
+        let close = GLOBAL_CANCEL.child_token();
 
+        tokio::spawn(async move {
             tokio::join!(
-                read(rx, &mut sender, close.clone()),
-                write(tx, &mut receiver, close)
+                read(rx, client, &sender, close.clone()),
+                write(tx, client, actions, &sender, close)
             );
-        })
+        });
     }
 }
 
-/// Reads commuinication from the GUI and sends it internally using a [`Sender`].
+/// Reads commuinication from a GUI and sends it internally using a [`Sender`].
 async fn read(
-    mut rx: OwnedReadHalf,
-    sender: &mut UnboundedSender<GuiResponse>,
+    mut rx: impl AsyncRead + Unpin,
+    client: ClientId,
+    sender: &UnboundedSender<(ClientId, GuiResponse)>,
     closed: CancellationToken,
 ) {
     closed
@@ -59,16 +85,15 @@ async fn read(
                 let response = match rx.read_obj().await {
                     Ok(response) => response,
                     Err(err) => {
-                        log::error!("GUI sent invalid data: {err}");
-                        closed.cancel();
-                        // TODO(tye): if this occurs try close the GUI (somehow).
-                        return;
+                        log::error!("Lost connection to GUI: {err}");
+                        // The tray closes the GUI if it is still running.
+                        GuiResponse::Crashed(err.to_string())
                     }
                 };
 
-                run = !matches!(response, GuiResponse::Closed);
+                run = !matches!(response, GuiResponse::Closed | GuiResponse::Crashed(_));
 
-                if let Err(_) = sender.send(response) {
+                if sender.send((client, response)).is_err() {
                     log::error!("Failure of internal communication.");
                     GLOBAL_CANCEL.cancel();
                 }
@@ -78,26 +103,38 @@ async fn read(
         .await;
 }
 
-/// Writes data to the GUI from an internal [`Receiver`].
+/// Writes the actions the tray sends to a GUI, other than those for other GUIs.
 async fn write(
-    mut tx: OwnedWriteHalf,
-    receiver: &mut UnboundedReceiver<GuiAction>,
+    mut tx: impl AsyncWrite + Unpin,
+    client: ClientId,
+    mut actions: broadcast::Receiver<Outgoing>,
+    sender: &UnboundedSender<(ClientId, GuiResponse)>,
     closed: CancellationToken,
 ) {
     closed
         .run_until_cancelled(async {
             let mut run = true;
             while run {
-                let action = match receiver.recv().await {
-                    Some(action) => action,
-                    None => {
+                let action = match actions.recv().await {
+                    Ok(Outgoing { to, action }) if to.is_none_or(|to| to == client) => action,
+                    Ok(_) => continue,
+                    Err(RecvError::Lagged(missed)) => {
+                        log::warn!("GUI fell behind the tray & missed {missed} actions");
+                        // The timers the GUI missed changes to are sent again.
+                        if sender.send((client, GuiResponse::Resync)).is_err() {
+                            log::error!("Failure of internal communication.");
+                            GLOBAL_CANCEL.cancel();
+                        }
+                        continue;
+                    }
+                    Err(RecvError::Closed) => {
                         log::error!("Failure of internal communication.");
                         GLOBAL_CANCEL.cancel();
                         return;
                     }
                 };
 
-                run = !matches!(action, GuiAction::Close);
+                run = !matches!(action, GuiAction::Close | GuiAction::Quit);
 
                 if let Err(err) = tx.write_obj(action).await {
                     log::error!("Unable to send data to GUI: {err}");
@@ -108,3 +145,165 @@ async fn write(
         })
         .await;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{BACKLOG, Outgoing, init_communication};
+    use crate::comms::{
+        GuiAction, GuiResponse,
+        async_socket::{AsyncReadObj as _, AsyncWriteObj as _},
+        transport::{MemoryTransport, Transport as _},
+    };
+    use tokio::sync::{broadcast, mpsc};
+
+    #[tokio::test]
+    async fn conversation() {
+        let transport = MemoryTransport::new();
+        let (tx_from_gui, mut rx_from_gui) = mpsc::unbounded_channel();
+        let (tx_to_gui, _) = broadcast::channel(BACKLOG);
+
+        let tray = tokio::spawn(init_communication(
+            tx_from_gui,
+            tx_to_gui.clone(),
+            transport.clone(),
+        ));
+
+        for _ in 0..2 {
+            let (mut rx, mut tx) =
+                MemoryTransport::split(transport.connect().await.expect("Can connect"));
+
+            tx.write_obj(GuiResponse::Opened).await.expect("Can write");
+            let (client, response) = rx_from_gui.recv().await.expect("Tray is running");
+            assert_eq!(response, GuiResponse::Opened);
+
+            tx_to_gui
+                .send(Outgoing {
+                    to: Some(client),
+                    action: GuiAction::Presets(Vec::new()),
+                })
+                .expect("GUI is connected");
+            assert_eq!(
+                rx.read_obj::<GuiAction>().await.expect("Can read"),
+                GuiAction::Presets(Vec::new())
+            );
+
+            tx_to_gui
+                .send(Outgoing {
+                    to: None,
+                    action: GuiAction::Close,
+                })
+                .expect("GUI is connected");
+            assert_eq!(
+                rx.read_obj::<GuiAction>().await.expect("Can read"),
+                GuiAction::Close
+            );
+
+            tx.write_obj(GuiResponse::Closed).await.expect("Can write");
+            let (_, response) = rx_from_gui.recv().await.expect("Tray is running");
+            assert_eq!(response, GuiResponse::Closed);
+        }
+
+        tray.abort();
+    }
+
+    #[tokio::test]
+    async fn several_clients() {
+        let transport = MemoryTransport::new();
+        let (tx_from_gui, mut rx_from_gui) = mpsc::unbounded_channel();
+        let (tx_to_gui, _) = broadcast::channel(BACKLOG);
+
+        let tray = tokio::spawn(init_communication(
+            tx_from_gui,
+            tx_to_gui.clone(),
+            transport.clone(),
+        ));
+
+        let (mut rx_first, mut tx_first) =
+            MemoryTransport::split(transport.connect().await.expect("Can connect"));
+        tx_first
+            .write_obj(GuiResponse::Opened)
+            .await
+            .expect("Can write");
+        let (first, _) = rx_from_gui.recv().await.expect("Tray is running");
+
+        // The first GUI is still connected whilst the second opens.
+        let (mut rx_second, mut tx_second) =
+            MemoryTransport::split(transport.connect().await.expect("Can connect"));
+        tx_second
+            .write_obj(GuiResponse::Resync)
+            .await
+            .expect("Can write");
+        let (second, response) = rx_from_gui.recv().await.expect("Tray is running");
+        assert_eq!(response, GuiResponse::Resync);
+        assert_ne!(first, second);
+
+        // Replies only go to the GUI they are for, whilst every GUI is sent the tray's other actions.
+        for (to, action) in [
+            (Some(second), GuiAction::Presets(Vec::new())),
+            (None, GuiAction::Quit),
+        ] {
+            tx_to_gui
+                .send(Outgoing { to, action })
+                .expect("GUIs are connected");
+        }
+        assert_eq!(
+            rx_first.read_obj::<GuiAction>().await.expect("Can read"),
+            GuiAction::Quit
+        );
+        assert_eq!(
+            rx_second.read_obj::<GuiAction>().await.expect("Can read"),
+            GuiAction::Presets(Vec::new())
+        );
+        assert_eq!(
+            rx_second.read_obj::<GuiAction>().await.expect("Can read"),
+            GuiAction::Quit
+        );
+
+        tx_first
+            .write_obj(GuiResponse::Closed)
+            .await
+            .expect("Can write");
+        tx_second
+            .write_obj(GuiResponse::Closed)
+            .await
+            .expect("Can write");
+        let mut closed = [
+            rx_from_gui.recv().await.expect("Tray is running"),
+            rx_from_gui.recv().await.expect("Tray is running"),
+        ]
+        .map(|(client, response)| {
+            assert_eq!(response, GuiResponse::Closed);
+            client
+        });
+        closed.sort();
+        assert_eq!(closed, [first, second]);
+
+        tray.abort();
+    }
+
+    #[tokio::test]
+    async fn lost_connection() {
+        let transport = MemoryTransport::new();
+        let (tx_from_gui, mut rx_from_gui) = mpsc::unbounded_channel();
+        let (tx_to_gui, _) = broadcast::channel(BACKLOG);
+
+        let tray = tokio::spawn(init_communication(
+            tx_from_gui,
+            tx_to_gui,
+            transport.clone(),
+        ));
+
+        let (_, mut tx) = MemoryTransport::split(transport.connect().await.expect("Can connect"));
+        tx.write_obj(GuiResponse::Opened).await.expect("Can write");
+        let (opened, response) = rx_from_gui.recv().await.expect("Tray is running");
+        assert_eq!(response, GuiResponse::Opened);
+
+        // The GUI dies without sending Closed.
+        drop(tx);
+        let (crashed, response) = rx_from_gui.recv().await.expect("Tray is running");
+        assert!(matches!(response, GuiResponse::Crashed(_)));
+        assert_eq!(crashed, opened);
+
+        tray.abort();
+    }
+}
@@ -0,0 +1,38 @@
+use std::sync::Arc;
+
+use tokio::sync::Mutex;
+
+use super::tray_icon::TimerTray;
+
+/// Makes changes to the [`TimerTray`], whether or not it is shown in the taskbar.
+#[derive(Clone)]
+pub(crate) enum TrayHandle {
+    /// The tray is shown by the desktop's StatusNotifier host.
+    Shown(ksni::Handle<TimerTray>),
+    /// The tray runs without an icon, so it can only be controlled by GUIs.
+    Headless(Arc<Mutex<TimerTray>>),
+}
+
+impl TrayHandle {
+    /// Runs the tray without an icon.
+    pub fn headless(tray: TimerTray) -> Self {
+        Self::Headless(Arc::new(Mutex::new(tray)))
+    }
+
+    /// Updates the tray, refreshing the icon if it is shown.
+    ///
+    /// Returns None if the tray has been shut down.
+    pub async fn update<R>(&self, update: impl FnOnce(&mut TimerTray) -> R) -> Option<R> {
+        match self {
+            Self::Shown(handle) => handle.update(update).await,
+            Self::Headless(tray) => Some(update(&mut *tray.lock().await)),
+        }
+    }
+
+    /// Removes the icon from the taskbar, if it is shown.
+    pub async fn shutdown(&self) {
+        if let Self::Shown(handle) = self {
+            handle.shutdown().await;
+        }
+    }
+}
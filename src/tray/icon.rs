@@ -0,0 +1,249 @@
+use std::path::Path;
+
+use image::{Rgba, RgbaImage, imageops::FilterType};
+
+use crate::timer::TimerData;
+
+/// The sizes the icon is rendered at, so the tray can pick the best fit for its scale.
+const SIZES: [u32; 5] = [16, 22, 32, 48, 64];
+
+/// The number of distinct positions the progress ring can be drawn at.
+const RING_STEPS: f32 = 64.0;
+
+/// What the tray icon is showing.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) enum IconState {
+    /// There are no timers to show.
+    Idle,
+    /// A running timer, with the fraction of it that has passed.
+    Running(f32),
+    /// A paused timer, with the fraction of it that has passed.
+    Paused(f32),
+    /// A timer that has ended.
+    Finished,
+}
+
+impl IconState {
+    /// The state shown for the given timer.
+    pub fn of(timer: &TimerData) -> Self {
+        if timer.finished() {
+            Self::Finished
+        } else if timer.paused() {
+            Self::Paused(timer.progress())
+        } else {
+            Self::Running(timer.progress())
+        }
+    }
+
+    /// Rounds the progress to the nearest step the ring can be drawn at.
+    fn quantised(self) -> Self {
+        let quantise = |progress: f32| (progress * RING_STEPS).round() / RING_STEPS;
+        match self {
+            Self::Running(progress) => Self::Running(quantise(progress)),
+            Self::Paused(progress) => Self::Paused(quantise(progress)),
+            state => state,
+        }
+    }
+}
+
+/// The base images the progress ring is drawn over, one for each kind of [`IconState`].
+struct IconSet {
+    idle: RgbaImage,
+    running: RgbaImage,
+    paused: RgbaImage,
+    finished: RgbaImage,
+}
+
+impl IconSet {
+    /// The icon embedded in the application, used for every state.
+    fn embedded() -> Self {
+        let icon = image::load_from_memory_with_format(
+            include_bytes!("icon.png"),
+            image::ImageFormat::Png,
+        )
+        .expect("'icon.png' is not a valid file")
+        .into_rgba8();
+
+        Self {
+            idle: icon.clone(),
+            running: icon.clone(),
+            paused: icon.clone(),
+            finished: icon,
+        }
+    }
+
+    /// Loads the user's icons from `idle.png`, `running.png`, `paused.png` & `finished.png` in the given directory.
+    ///
+    /// Any icon that cannot be loaded is replaced with the embedded icon.
+    fn load(dir: &Path) -> Self {
+        let embedded = Self::embedded();
+        let load = |name: &str, fallback: RgbaImage| {
+            let path = dir.join(name);
+            image::open(&path)
+                .map(|image| image.into_rgba8())
+                .inspect_err(|err| log::warn!("Unable to load icon '{}': {err}", path.display()))
+                .unwrap_or(fallback)
+        };
+
+        Self {
+            idle: load("idle.png", embedded.idle),
+            running: load("running.png", embedded.running),
+            paused: load("paused.png", embedded.paused),
+            finished: load("finished.png", embedded.finished),
+        }
+    }
+
+    fn get(&self, state: IconState) -> &RgbaImage {
+        match state {
+            IconState::Idle => &self.idle,
+            IconState::Running(_) => &self.running,
+            IconState::Paused(_) => &self.paused,
+            IconState::Finished => &self.finished,
+        }
+    }
+}
+
+/// Renders the tray icon, only redrawing it when what it shows changes.
+pub(crate) struct TrayIcon {
+    icons: IconSet,
+    /// The state the icon was last rendered for.
+    state: Option<IconState>,
+    /// The rendered icon, in every size.
+    rendered: Vec<ksni::Icon>,
+    /// The icon shown when a timer needs the user's attention, in every size.
+    attention: Vec<ksni::Icon>,
+}
+
+impl TrayIcon {
+    /// Creates an icon using the user's icons in the given directory, or the embedded icon.
+    pub fn new(dir: Option<&Path>) -> Self {
+        let icons = dir.map(IconSet::load).unwrap_or_else(IconSet::embedded);
+        let attention = render_sizes(&icons, IconState::Finished);
+
+        Self {
+            icons,
+            state: None,
+            rendered: Vec::new(),
+            attention,
+        }
+    }
+
+    /// Shows the given state, redrawing the icon if it changed.
+    ///
+    /// Returns whether the icon was redrawn.
+    pub fn update(&mut self, state: IconState) -> bool {
+        let state = state.quantised();
+        if self.state == Some(state) {
+            return false;
+        }
+
+        self.rendered = render_sizes(&self.icons, state);
+        self.state = Some(state);
+        true
+    }
+
+    /// The icon in every size it was rendered at.
+    pub fn pixmaps(&self) -> Vec<ksni::Icon> {
+        self.rendered.clone()
+    }
+
+    /// The icon shown when a timer needs the user's attention, in every size.
+    pub fn attention_pixmaps(&self) -> Vec<ksni::Icon> {
+        self.attention.clone()
+    }
+}
+
+/// Renders the icon for the state in every size.
+fn render_sizes(icons: &IconSet, state: IconState) -> Vec<ksni::Icon> {
+    SIZES
+        .iter()
+        .map(|size| to_icon(render(icons.get(state), state, *size)))
+        .collect()
+}
+
+/// Draws the progress ring for the state over the base image, at the given size.
+fn render(base: &RgbaImage, state: IconState, size: u32) -> RgbaImage {
+    let mut image = image::imageops::resize(base, size, size, FilterType::Triangle);
+
+    let (colour, remaining) = match state {
+        IconState::Idle => return image,
+        IconState::Running(progress) => (Rgba([100, 180, 255, 255]), 1.0 - progress),
+        IconState::Paused(progress) => (Rgba([240, 180, 40, 255]), 1.0 - progress),
+        IconState::Finished => (Rgba([230, 60, 50, 255]), 1.0),
+    };
+    let track = Rgba([60, 60, 60, 200]);
+
+    let centre = size as f32 / 2.0;
+    let outer = centre;
+    let inner = outer - (size as f32 / 6.0).max(2.0);
+
+    for (x, y, pixel) in image.enumerate_pixels_mut() {
+        let (dx, dy) = (x as f32 + 0.5 - centre, y as f32 + 0.5 - centre);
+        let distance = dx.hypot(dy);
+        if distance < inner || distance > outer {
+            continue;
+        }
+
+        // The fraction of the way around the ring, clockwise from the top.
+        let around = (dx.atan2(-dy) / std::f32::consts::TAU).rem_euclid(1.0);
+        *pixel = if around < remaining { colour } else { track };
+    }
+
+    image
+}
+
+/// Converts the image into the ARGB icon used by the tray.
+fn to_icon(image: RgbaImage) -> ksni::Icon {
+    let (width, height) = image.dimensions();
+    let mut data = image.into_vec();
+
+    for pixel in data.chunks_exact_mut(4) {
+        pixel.rotate_right(1) // rgba to argb
+    }
+
+    ksni::Icon {
+        width: width as i32,
+        height: height as i32,
+        data,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgba;
+
+    use super::{IconSet, IconState, SIZES, TrayIcon, render};
+
+    #[test]
+    fn only_redraws_on_change() {
+        let mut icon = TrayIcon::new(None);
+
+        assert!(icon.update(IconState::Running(0.5)));
+        assert_eq!(icon.pixmaps().len(), SIZES.len());
+
+        assert!(!icon.update(IconState::Running(0.5001)));
+        assert!(icon.update(IconState::Paused(0.5)));
+        assert!(icon.update(IconState::Finished));
+        assert!(!icon.update(IconState::Finished));
+    }
+
+    #[test]
+    fn ring_shows_remaining_time() {
+        let icons = IconSet::embedded();
+        let image = render(&icons.running, IconState::Running(0.75), 32);
+
+        // A quarter of the time remains, so only the top right of the ring is coloured.
+        assert_eq!(*image.get_pixel(24, 2), Rgba([100, 180, 255, 255]));
+        assert_eq!(*image.get_pixel(8, 2), Rgba([60, 60, 60, 200]));
+        assert_eq!(*image.get_pixel(24, 29), Rgba([60, 60, 60, 200]));
+
+        let image = render(&icons.finished, IconState::Finished, 32);
+        assert_eq!(*image.get_pixel(8, 29), Rgba([230, 60, 50, 255]));
+    }
+
+    #[test]
+    fn argb() {
+        let icon = super::to_icon(image::RgbaImage::from_pixel(1, 1, Rgba([1, 2, 3, 4])));
+        assert_eq!(icon.data, [4, 1, 2, 3]);
+    }
+}
@@ -0,0 +1,227 @@
+//! Stops the computer from sleeping whilst a timer that opted in is about to end, so its alarm is not missed.
+//!
+//! This holds a logind inhibitor lock, which is released when its file descriptor is closed.
+
+use std::os::fd::{AsRawFd as _, OwnedFd};
+
+use tokio::sync::watch;
+use zbus::Connection;
+
+use super::tray_icon::APP_NAME;
+
+#[zbus::proxy(
+    interface = "org.freedesktop.login1.Manager",
+    default_service = "org.freedesktop.login1",
+    default_path = "/org/freedesktop/login1"
+)]
+trait LoginManager {
+    fn inhibit(
+        &self,
+        what: &str,
+        who: &str,
+        why: &str,
+        mode: &str,
+    ) -> zbus::Result<zbus::zvariant::OwnedFd>;
+}
+
+/// Holds an inhibitor lock whenever the tray wants the computer kept awake.
+///
+/// This runs until the tray stops sending whether it wants the computer kept awake.
+pub(crate) async fn hold(wanted: watch::Receiver<bool>) {
+    let result = match Connection::system().await {
+        Ok(connection) => hold_with(&connection, wanted).await,
+        Err(err) => Err(err),
+    };
+    if let Err(err) = result {
+        log::warn!("Unable to keep the computer awake: {err}");
+    }
+}
+
+/// Holds an inhibitor lock from the logind on the given bus whenever the tray wants the computer kept awake.
+async fn hold_with(connection: &Connection, mut wanted: watch::Receiver<bool>) -> zbus::Result<()> {
+    let proxy = LoginManagerProxy::new(connection).await?;
+    let mut lock: Option<OwnedFd> = None;
+
+    loop {
+        let wanted_now = *wanted.borrow_and_update();
+
+        if wanted_now && lock.is_none() {
+            match proxy
+                .inhibit(
+                    "sleep:idle",
+                    APP_NAME,
+                    "A timer is about to finish",
+                    "block",
+                )
+                .await
+            {
+                Ok(fd) => {
+                    log::info!("Keeping the computer awake");
+                    lock = Some(close_on_exec(fd.into()));
+                }
+                // The lock is asked for again when the timers change.
+                Err(err) => log::warn!("Unable to keep the computer awake: {err}"),
+            }
+        } else if !wanted_now && lock.take().is_some() {
+            log::info!("Allowing the computer to sleep");
+        }
+
+        if wanted.changed().await.is_err() {
+            return Ok(());
+        }
+    }
+}
+
+/// Keeps the lock from being inherited by the GUI & other processes the tray spawns, which would hold it until they
+/// exit.
+fn close_on_exec(fd: OwnedFd) -> OwnedFd {
+    // SAFETY: The file descriptor is owned, so it stays open for the duration of the call.
+    if unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_SETFD, libc::FD_CLOEXEC) } == -1 {
+        log::warn!(
+            "Unable to stop the lock being inherited: {}",
+            std::io::Error::last_os_error()
+        );
+    }
+    fd
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{ErrorKind, Read as _},
+        os::{
+            fd::{AsRawFd as _, OwnedFd},
+            unix::net::UnixStream,
+        },
+        process::Command,
+        time::Duration,
+    };
+
+    use tokio::sync::watch;
+
+    use super::{close_on_exec, hold_with};
+    use crate::tray::test_bus::Bus;
+
+    /// A mock of logind, which hands out one end of a socket for each lock.
+    #[derive(Default)]
+    struct MockLogind {
+        /// What each lock inhibits & how, along with the other end of its socket.
+        locks: Vec<(String, String, UnixStream)>,
+    }
+
+    #[zbus::interface(name = "org.freedesktop.login1.Manager")]
+    impl MockLogind {
+        fn inhibit(
+            &mut self,
+            what: String,
+            _who: String,
+            _why: String,
+            mode: String,
+        ) -> zbus::fdo::Result<zbus::zvariant::OwnedFd> {
+            let (ours, theirs) =
+                UnixStream::pair().map_err(|err| zbus::fdo::Error::Failed(err.to_string()))?;
+            ours.set_nonblocking(true)
+                .map_err(|err| zbus::fdo::Error::Failed(err.to_string()))?;
+            self.locks.push((what, mode, ours));
+            Ok(OwnedFd::from(theirs).into())
+        }
+    }
+
+    /// Whether the tray has closed its end of the lock's socket.
+    fn released(mut lock: &UnixStream) -> bool {
+        match lock.read(&mut [0]) {
+            Ok(0) => true,
+            Err(err) if err.kind() == ErrorKind::WouldBlock => false,
+            other => panic!("Unexpected read from lock: {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn holds_lock_whilst_wanted() {
+        let Some(bus) = Bus::start() else {
+            eprintln!("dbus-daemon is not installed, skipping");
+            return;
+        };
+
+        let logind = bus.connect(Some("org.freedesktop.login1")).await;
+        logind
+            .object_server()
+            .at("/org/freedesktop/login1", MockLogind::default())
+            .await
+            .expect("Can serve mock");
+        let tray = bus.connect(None).await;
+
+        let (sender, receiver) = watch::channel(false);
+        let hold = tokio::spawn(async move { hold_with(&tray, receiver).await });
+
+        let mock = logind
+            .object_server()
+            .interface::<_, MockLogind>("/org/freedesktop/login1")
+            .await
+            .expect("Mock is served");
+
+        // Waits for the condition to hold for the mock, failing after a few seconds.
+        let wait_for = |condition: fn(&MockLogind) -> bool| {
+            let mock = mock.clone();
+            async move {
+                for _ in 0..100 {
+                    if condition(&*mock.get().await) {
+                        return;
+                    }
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                }
+                panic!("Mock logind did not reach the expected state");
+            }
+        };
+
+        sender.send_replace(true);
+        wait_for(|mock| mock.locks.len() == 1).await;
+        {
+            let mock = mock.get().await;
+            let (what, mode, lock) = &mock.locks[0];
+            assert_eq!((what.as_str(), mode.as_str()), ("sleep:idle", "block"));
+            assert!(!released(lock));
+        }
+
+        // A process spawned whilst the lock is held does not keep it held.
+        let mut child = Command::new("sleep")
+            .arg("30")
+            .spawn()
+            .expect("Can spawn child");
+
+        sender.send_replace(false);
+        wait_for(|mock| released(&mock.locks[0].2)).await;
+        let _ = child.kill();
+        let _ = child.wait();
+
+        // The lock is taken again the next time it is wanted.
+        sender.send_replace(true);
+        wait_for(|mock| mock.locks.len() == 2).await;
+
+        drop(sender);
+        hold.await
+            .expect("Task did not panic")
+            .expect("Can talk to logind");
+        wait_for(|mock| released(&mock.locks[1].2)).await;
+    }
+
+    /// Whether the file descriptor is closed when the process spawns another.
+    fn closes_on_exec(fd: &OwnedFd) -> bool {
+        // SAFETY: The file descriptor is owned, so it stays open for the duration of the call.
+        let flags = unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_GETFD) };
+        flags & libc::FD_CLOEXEC != 0
+    }
+
+    #[test]
+    fn locks_close_on_exec() {
+        let (lock, _other) = UnixStream::pair().expect("Can create socket");
+        let lock = OwnedFd::from(lock);
+
+        // Locks received from logind do not have the flag set.
+        // SAFETY: The file descriptor is owned, so it stays open for the duration of the call.
+        unsafe { libc::fcntl(lock.as_raw_fd(), libc::F_SETFD, 0) };
+        assert!(!closes_on_exec(&lock));
+
+        assert!(closes_on_exec(&close_on_exec(lock)));
+    }
+}
@@ -1,41 +1,196 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")] // hide console window on Windows in release
 
+use crate::{
+    comms::{
+        SOCKET_ADDR, TransportKind, capture, socket_path,
+        transport::{PipeTransport, TcpTransport, UnixTransport},
+    },
+    systemd,
+};
+use clap::ValueEnum as _;
 use comms::init_communication;
+use handle::TrayHandle;
 use ksni::TrayMethods;
-use std::sync::LazyLock;
-use tokio::sync::mpsc::{self};
+use std::{
+    io::ErrorKind,
+    os::{fd::OwnedFd, unix::net::UnixStream},
+    path::PathBuf,
+    process::{Child, Stdio},
+    sync::LazyLock,
+    time::Duration,
+};
+use tokio::sync::{
+    broadcast,
+    mpsc::{self, UnboundedSender},
+    watch,
+};
 use tokio_util::sync::CancellationToken;
-use tray_icon::{TimerTray, update_tray};
+use tray_icon::{
+    TaskSenders, TimerTray, tick_tray, update_tray, watch_presence, watch_shortcuts, watch_signals,
+};
 
 mod comms;
+mod handle;
+mod icon;
+mod inhibit;
+mod mpris;
+mod notify;
+mod portal;
+mod session;
+mod storage;
+#[cfg(test)]
+mod test_bus;
 mod tray_icon;
 
 /// The [`CancellationToken`] that is responsible for shutting down the entire application when it is cancelled.
-static GLOBAL_CANCEL: LazyLock<CancellationToken> = LazyLock::new(|| CancellationToken::new());
+static GLOBAL_CANCEL: LazyLock<CancellationToken> = LazyLock::new(CancellationToken::new);
 
-pub(crate) fn launch_tray() {
+/// Options for how the tray runs.
+pub(crate) struct TrayOptions {
+    /// How the GUI connects to the tray.
+    pub transport: TransportKind,
+    /// A directory containing the user's own tray icons.
+    pub icons: Option<PathBuf>,
+    /// Whether to open the GUI again if it crashes.
+    pub restart_gui: bool,
+    /// Whether to run without a tray icon or opening a GUI.
+    pub daemon: bool,
+    /// Whether to open the GUI when the tray starts.
+    pub open_gui: bool,
+    /// Listening sockets passed in by systemd, used instead of binding a socket.
+    pub listeners: Vec<OwnedFd>,
+}
+
+pub(crate) fn launch_tray(options: TrayOptions) {
     tokio::runtime::Builder::new_multi_thread()
         .enable_all()
         .build()
         .unwrap()
-        .block_on(start());
+        .block_on(start(options));
 }
 
-async fn start() {
-    let (tx_to_gui, rx_to_gui) = mpsc::unbounded_channel();
+async fn start(mut options: TrayOptions) {
+    let transport = options.transport;
+
+    if options.listeners.len() > 1 {
+        log::warn!("Only the first socket passed in by systemd is used");
+    }
+    let listener = options.listeners.drain(..).next();
+    let (tx_to_gui, _) = broadcast::channel(comms::BACKLOG);
     let (tx_from_gui, rx_from_gui) = mpsc::unbounded_channel();
+    let (responses, rx_responses) = mpsc::unbounded_channel();
+
+    let pipes = match transport {
+        TransportKind::Tcp => {
+            let mut transport = TcpTransport::new(SOCKET_ADDR);
+            if let Some(listener) = listener {
+                transport = transport.with_listener(listener.into());
+            }
+            tokio::spawn(init_communication(
+                tx_from_gui,
+                tx_to_gui.clone(),
+                transport,
+            ));
+            None
+        }
+        TransportKind::Unix => {
+            let mut transport = UnixTransport::new(socket_path());
+            if let Some(listener) = listener {
+                transport = transport.with_listener(listener.into());
+            }
+            tokio::spawn(init_communication(
+                tx_from_gui,
+                tx_to_gui.clone(),
+                transport,
+            ));
+            None
+        }
+        TransportKind::Pipe => {
+            if listener.is_some() {
+                log::warn!("The socket passed in by systemd is not used by the pipe transport");
+            }
+            let (transport, pipes) = PipeTransport::new();
+            tokio::spawn(init_communication(
+                tx_from_gui,
+                tx_to_gui.clone(),
+                transport,
+            ));
+            Some(pipes)
+        }
+    };
 
-    tokio::spawn(init_communication(tx_from_gui, rx_to_gui));
-    spawn_gui();
+    let (tx_keep_awake, rx_keep_awake) = watch::channel(false);
+    tokio::spawn(inhibit::hold(rx_keep_awake));
 
-    let handle = TimerTray::new(tx_to_gui)
-        .spawn()
-        .await
-        .expect("Unable to start taskbar tray.");
+    let (tx_player, rx_player) = watch::channel(None);
+    tokio::spawn(mpris::serve(rx_player, responses.clone()));
+
+    let (tx_shortcuts, rx_shortcuts) = watch::channel(Default::default());
+
+    let (tx_notifications, rx_notifications) = mpsc::unbounded_channel();
+    tokio::spawn(notify::show(rx_notifications));
+
+    let launcher = GuiLauncher { transport, pipes };
+    let tasks = TaskSenders {
+        keep_awake: tx_keep_awake,
+        player: tx_player,
+        shortcuts: tx_shortcuts,
+        notifications: tx_notifications,
+    };
+    let persistent = storage::load();
+    let new_tray = |persistent| {
+        TimerTray::new(
+            tx_to_gui.clone(),
+            responses.clone(),
+            launcher.clone(),
+            tasks.clone(),
+            persistent,
+            &options,
+        )
+    };
+
+    let handle = match options.daemon {
+        true => TrayHandle::headless(new_tray(persistent)),
+        // The tray is dropped if it cannot be shown, so the icon is given a copy of the loaded state.
+        false => match new_tray(persistent.clone()).spawn().await {
+            Ok(handle) => TrayHandle::Shown(handle),
+            Err(err) => {
+                log::warn!("Unable to show tray icon, running without one: {err}");
+                TrayHandle::headless(new_tray(persistent))
+            }
+        },
+    };
+    handle.update(TimerTray::start).await;
+
+    // A daemon waits for the user to open a GUI themselves.
+    if !options.daemon {
+        if options.open_gui {
+            handle.update(TimerTray::open_gui).await;
+        }
+    } else if transport == TransportKind::Pipe {
+        log::warn!("GUIs can only connect to a daemon with the tcp or unix transports");
+    }
+
+    tokio::spawn(update_tray(handle.clone(), rx_from_gui, rx_responses));
+    tokio::spawn(watch_signals(handle.clone()));
+    tokio::spawn(tick_tray(handle.clone()));
 
-    tokio::spawn(update_tray(handle.clone(), rx_from_gui));
+    let (tx_presence, rx_presence) = mpsc::unbounded_channel();
+    tokio::spawn(session::watch(tx_presence));
+    tokio::spawn(watch_presence(handle.clone(), rx_presence));
+
+    // The shortcuts are bound once the tray has loaded the user's choice of keys.
+    let (tx_shortcut, rx_shortcut) = mpsc::unbounded_channel();
+    tokio::spawn(portal::bind(rx_shortcuts, tx_shortcut));
+    tokio::spawn(watch_shortcuts(handle.clone(), rx_shortcut));
+
+    if let Err(err) = systemd::notify("READY=1") {
+        log::error!("Unable to notify systemd that the tray is ready: {err}");
+    }
 
     GLOBAL_CANCEL.cancelled().await;
+    let _ = systemd::notify("STOPPING=1");
+    handle.update(TimerTray::save).await;
     handle.shutdown().await;
 }
 
@@ -48,22 +203,75 @@ enum GuiState {
     Closed,
 }
 
-/// Creates a new gui.
-fn spawn_gui() {
-    let Ok(exe_path) = std::env::current_exe() else {
-        return;
-    };
-    std::process::Command::new(exe_path)
-        .arg("--gui")
-        .spawn()
-        .unwrap();
+/// Spawns GUIs connected to the tray with the configured [`TransportKind`].
+#[derive(Clone)]
+pub(crate) struct GuiLauncher {
+    transport: TransportKind,
+    /// Passes the tray's end of the socket pair to the [`PipeTransport`] when using [`TransportKind::Pipe`].
+    pipes: Option<UnboundedSender<UnixStream>>,
+}
+
+impl GuiLauncher {
+    /// Creates a new gui.
+    fn spawn(&self) -> std::io::Result<Child> {
+        let exe_path = std::env::current_exe()?;
+        let mut command = std::process::Command::new(exe_path);
+        command.arg("--gui");
+
+        if let Some(transport) = self.transport.to_possible_value() {
+            command.arg("--transport").arg(transport.get_name());
+        }
+
+        // The GUI records to the same capture as the tray.
+        if let Some(path) = capture::path() {
+            command.arg("--capture").arg(path);
+        }
+
+        let Some(pipes) = &self.pipes else {
+            return command.spawn();
+        };
+
+        let (tray_end, gui_end) = UnixStream::pair()?;
+        command.stdin(Stdio::from(OwnedFd::from(gui_end)));
+        let child = command.spawn()?;
+
+        // The tray only listens on its end once the GUI is running.
+        if pipes.send(tray_end).is_err() {
+            GLOBAL_CANCEL.cancel();
+            return Err(std::io::Error::new(
+                ErrorKind::BrokenPipe,
+                "Internal tray communication was closed unexpectedly",
+            ));
+        }
+        Ok(child)
+    }
+}
+
+/// Asks the user for the length of a new timer with a small window.
+///
+/// If the user cancels the prompt, then no duration is returned.
+async fn prompt_duration() -> Option<Duration> {
+    let exe_path = std::env::current_exe().ok()?;
+    let output = tokio::process::Command::new(exe_path)
+        .arg("--prompt")
+        .stderr(Stdio::inherit())
+        .output()
+        .await
+        .inspect_err(|err| log::error!("Unable to prompt for timer: {err}"))
+        .ok()?;
+
+    let secs = String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse()
+        .ok()?;
+    Some(Duration::from_secs(secs))
 }
 
 /// Runs the given future until [`GLOBAL_CANCEL`] is cancelled.
 #[macro_export]
 macro_rules! until_global_cancel {
     ($future:expr) => {
-        (match crate::tray::GLOBAL_CANCEL
+        (match $crate::tray::GLOBAL_CANCEL
             .run_until_cancelled($future)
             .await
         {
@@ -0,0 +1,494 @@
+//! Exposes the timer shown by the tray icon as an MPRIS media player, so media keys & desktop media widgets can
+//! control it.
+//!
+//! Playing & pausing the player resumes & pauses the timer, and stopping it resets the timer.
+
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use tokio::sync::{mpsc::UnboundedSender, watch};
+use zbus::{
+    Connection,
+    object_server::SignalEmitter,
+    zvariant::{ObjectPath, OwnedValue, Value},
+};
+
+use super::tray_icon::APP_NAME;
+use crate::{
+    comms::{
+        GuiResponse,
+        timer_sync::{TimerCommand, TimerId},
+    },
+    timer::TimerData,
+};
+
+/// The name the player is registered under.
+const BUS_NAME: &str = concat!("org.mpris.MediaPlayer2.", env!("CARGO_PKG_NAME"));
+
+/// The path of the player, as required by MPRIS.
+const PATH: &str = "/org/mpris/MediaPlayer2";
+
+/// How far the position can differ from the time passed before the player signals that it was seeked.
+const SEEK_TOLERANCE: Duration = Duration::from_millis(500);
+
+/// The timer the player controls.
+#[derive(Clone, PartialEq, Debug)]
+pub(crate) struct Track {
+    id: TimerId,
+    title: String,
+    /// How much time has passed.
+    position: Duration,
+    /// After how long the timer ends.
+    length: Duration,
+    status: PlaybackStatus,
+}
+
+impl Track {
+    pub fn of(id: TimerId, title: String, timer: &TimerData) -> Self {
+        let status = if timer.finished() || (timer.paused() && timer.duration().is_zero()) {
+            PlaybackStatus::Stopped
+        } else if timer.paused() {
+            PlaybackStatus::Paused
+        } else {
+            PlaybackStatus::Playing
+        };
+
+        Self {
+            id,
+            title,
+            // Overtime is past the end of the track.
+            position: timer.duration().min(timer.end_after()),
+            length: timer.end_after(),
+            status,
+        }
+    }
+
+    /// The MPRIS metadata describing the timer.
+    fn metadata(&self) -> HashMap<String, OwnedValue> {
+        let track_id =
+            ObjectPath::try_from(format!("/{}/timer/{}", env!("CARGO_PKG_NAME"), self.id.0))
+                .expect("Track id is a valid path");
+
+        [
+            ("mpris:trackid", Value::from(track_id)),
+            ("mpris:length", Value::from(micros(self.length))),
+            ("xesam:title", Value::from(self.title.as_str())),
+            ("xesam:artist", Value::from(vec![APP_NAME])),
+        ]
+        .into_iter()
+        .map(|(key, value)| {
+            let value = OwnedValue::try_from(value).expect("Value does not hold a file descriptor");
+            (key.to_string(), value)
+        })
+        .collect()
+    }
+}
+
+/// Whether the timer is running, as MPRIS describes it.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum PlaybackStatus {
+    Playing,
+    Paused,
+    Stopped,
+}
+
+impl PlaybackStatus {
+    fn as_str(self) -> &'static str {
+        match self {
+            PlaybackStatus::Playing => "Playing",
+            PlaybackStatus::Paused => "Paused",
+            PlaybackStatus::Stopped => "Stopped",
+        }
+    }
+}
+
+/// A time as the microseconds MPRIS uses.
+fn micros(time: Duration) -> i64 {
+    time.as_micros().try_into().unwrap_or(i64::MAX)
+}
+
+/// The `org.mpris.MediaPlayer2` interface, which describes the application.
+struct MediaPlayer;
+
+#[zbus::interface(name = "org.mpris.MediaPlayer2")]
+impl MediaPlayer {
+    fn raise(&self) {}
+
+    fn quit(&self) {}
+
+    #[zbus(property)]
+    fn can_quit(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn can_raise(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn has_track_list(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn identity(&self) -> &str {
+        APP_NAME
+    }
+
+    #[zbus(property)]
+    fn desktop_entry(&self) -> &str {
+        env!("CARGO_PKG_NAME")
+    }
+
+    #[zbus(property)]
+    fn supported_uri_schemes(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    #[zbus(property)]
+    fn supported_mime_types(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+/// The `org.mpris.MediaPlayer2.Player` interface, which controls the timer.
+struct Player {
+    track: watch::Receiver<Option<Track>>,
+    /// Performs the commands on the tray's timers.
+    responses: UnboundedSender<GuiResponse>,
+}
+
+impl Player {
+    /// Sends the commands for the current timer to the tray, if there is one.
+    fn command(&self, commands: impl FnOnce(&Track) -> Vec<TimerCommand>) {
+        let Some(track) = &*self.track.borrow() else {
+            return;
+        };
+
+        for command in commands(track) {
+            let _ = self.responses.send(GuiResponse::Timer(command));
+        }
+    }
+}
+
+#[zbus::interface(name = "org.mpris.MediaPlayer2.Player")]
+impl Player {
+    fn play(&self) {
+        self.command(|track| vec![TimerCommand::Pause(track.id, false)]);
+    }
+
+    fn pause(&self) {
+        self.command(|track| vec![TimerCommand::Pause(track.id, true)]);
+    }
+
+    fn play_pause(&self) {
+        self.command(|track| {
+            let pause = track.status == PlaybackStatus::Playing;
+            vec![TimerCommand::Pause(track.id, pause)]
+        });
+    }
+
+    fn stop(&self) {
+        self.command(|track| {
+            vec![
+                TimerCommand::Reset(track.id),
+                TimerCommand::Pause(track.id, true),
+            ]
+        });
+    }
+
+    fn next(&self) {}
+
+    fn previous(&self) {}
+
+    fn seek(&self, _offset: i64) {}
+
+    fn set_position(&self, _track_id: ObjectPath<'_>, _position: i64) {}
+
+    fn open_uri(&self, _uri: &str) {}
+
+    /// Signals that the position jumped, such as when the timer is reset, rather than time passing.
+    #[zbus(signal)]
+    async fn seeked(emitter: &SignalEmitter<'_>, position: i64) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn playback_status(&self) -> &str {
+        match &*self.track.borrow() {
+            Some(track) => track.status.as_str(),
+            None => PlaybackStatus::Stopped.as_str(),
+        }
+    }
+
+    #[zbus(property)]
+    fn metadata(&self) -> HashMap<String, OwnedValue> {
+        match &*self.track.borrow() {
+            Some(track) => track.metadata(),
+            None => HashMap::new(),
+        }
+    }
+
+    /// Clients work out the position of a playing timer from its rate, so changes are not signalled.
+    #[zbus(property(emits_changed_signal = "false"))]
+    fn position(&self) -> i64 {
+        self.track
+            .borrow()
+            .as_ref()
+            .map_or(0, |track| micros(track.position))
+    }
+
+    #[zbus(property)]
+    fn rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn minimum_rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn maximum_rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn can_go_next(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn can_go_previous(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn can_play(&self) -> bool {
+        self.track.borrow().is_some()
+    }
+
+    #[zbus(property)]
+    fn can_pause(&self) -> bool {
+        self.track.borrow().is_some()
+    }
+
+    #[zbus(property)]
+    fn can_seek(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn can_control(&self) -> bool {
+        true
+    }
+}
+
+/// Registers the player on the session bus, keeping it up to date with the timer shown by the tray icon.
+///
+/// This runs until the tray stops sending the timer it shows.
+pub(crate) async fn serve(
+    track: watch::Receiver<Option<Track>>,
+    responses: UnboundedSender<GuiResponse>,
+) {
+    let result = match Connection::session().await {
+        Ok(connection) => serve_on(&connection, track, responses).await,
+        Err(err) => Err(err),
+    };
+    if let Err(err) = result {
+        log::warn!("Unable to control timers with media keys: {err}");
+    }
+}
+
+/// Registers the player on the given bus, keeping it up to date with the timer shown by the tray icon.
+async fn serve_on(
+    connection: &Connection,
+    mut track: watch::Receiver<Option<Track>>,
+    responses: UnboundedSender<GuiResponse>,
+) -> zbus::Result<()> {
+    let object_server = connection.object_server();
+    object_server.at(PATH, MediaPlayer).await?;
+    object_server
+        .at(
+            PATH,
+            Player {
+                track: track.clone(),
+                responses,
+            },
+        )
+        .await?;
+    connection.request_name(BUS_NAME).await?;
+
+    let player = object_server.interface::<_, Player>(PATH).await?;
+    let mut shown = track.borrow_and_update().clone();
+    let mut shown_at = Instant::now();
+
+    // Only changes to the timer shown or whether it is running are signalled, not every tick.
+    while track.changed().await.is_ok() {
+        let current = track.borrow_and_update().clone();
+        let seeked = match (&shown, &current) {
+            (Some(shown), Some(current)) if shown.id == current.id => {
+                let passed = match shown.status {
+                    PlaybackStatus::Playing => shown_at.elapsed(),
+                    PlaybackStatus::Paused | PlaybackStatus::Stopped => Duration::ZERO,
+                };
+                let expected = (shown.position + passed).min(current.length);
+                (current.position.abs_diff(expected) > SEEK_TOLERANCE).then_some(current.position)
+            }
+            _ => None,
+        };
+        let status = |track: &Option<Track>| track.as_ref().map(|track| track.status);
+        let described = |track: &Option<Track>| {
+            track
+                .as_ref()
+                .map(|track| (track.id, track.title.clone(), track.length))
+        };
+
+        let emitter = player.signal_emitter();
+        let changed_status = status(&current) != status(&shown);
+        let changed_metadata = described(&current) != described(&shown);
+        shown = current;
+        shown_at = Instant::now();
+
+        if let Some(position) = seeked {
+            Player::seeked(emitter, micros(position)).await?;
+        }
+
+        let player = player.get().await;
+        if changed_status {
+            player.playback_status_changed(emitter).await?;
+        }
+        if changed_metadata {
+            player.metadata_changed(emitter).await?;
+            player.can_play_changed(emitter).await?;
+            player.can_pause_changed(emitter).await?;
+        }
+    }
+
+    connection.release_name(BUS_NAME).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures_util::StreamExt as _;
+    use tokio::sync::{mpsc, watch};
+
+    use super::{BUS_NAME, PATH, Track, serve_on};
+    use crate::{
+        comms::{
+            GuiResponse,
+            timer_sync::{TimerCommand, TimerId},
+        },
+        timer::TimerData,
+        tray::test_bus::Bus,
+    };
+
+    #[tokio::test]
+    async fn media_keys_control_timer() {
+        let Some(bus) = Bus::start() else {
+            eprintln!("dbus-daemon is not installed, skipping");
+            return;
+        };
+
+        let mut timer = TimerData::new(Duration::from_secs(60));
+        let (sender, receiver) = watch::channel(Some(Track::of(TimerId(3), "Tea".into(), &timer)));
+        let (responses, mut commands) = mpsc::unbounded_channel();
+
+        let tray = bus.connect(None).await;
+        let serve = tokio::spawn(async move { serve_on(&tray, receiver, responses).await });
+
+        // A desktop media widget.
+        let widget = bus.connect(None).await;
+        let player: zbus::Proxy = zbus::proxy::Builder::new(&widget)
+            .destination(BUS_NAME)
+            .and_then(|builder| builder.path(PATH))
+            .and_then(|builder| builder.interface("org.mpris.MediaPlayer2.Player"))
+            .expect("Valid proxy")
+            .cache_properties(zbus::proxy::CacheProperties::No)
+            .build()
+            .await
+            .expect("Can create proxy");
+
+        // The player may not have been registered yet.
+        let mut status = None;
+        for _ in 0..100 {
+            if let Ok(playing) = player.get_property::<String>("PlaybackStatus").await {
+                status = Some(playing);
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        assert_eq!(status.as_deref(), Some("Playing"));
+
+        let metadata: std::collections::HashMap<String, zbus::zvariant::OwnedValue> =
+            player.get_property("Metadata").await.expect("Has metadata");
+        let title: String = metadata["xesam:title"]
+            .try_clone()
+            .expect("Can clone")
+            .try_into()
+            .expect("Title is a string");
+        assert_eq!(title, "Tea");
+
+        player
+            .call_method("PlayPause", &())
+            .await
+            .expect("Can pause");
+        assert_eq!(
+            commands.recv().await,
+            Some(GuiResponse::Timer(TimerCommand::Pause(TimerId(3), true)))
+        );
+
+        // The tray pauses the timer.
+        timer.pause(true);
+        sender.send_replace(Some(Track::of(TimerId(3), "Tea".into(), &timer)));
+        player.call_method("Stop", &()).await.expect("Can stop");
+        assert_eq!(
+            commands.recv().await,
+            Some(GuiResponse::Timer(TimerCommand::Reset(TimerId(3))))
+        );
+        assert_eq!(
+            commands.recv().await,
+            Some(GuiResponse::Timer(TimerCommand::Pause(TimerId(3), true)))
+        );
+
+        // Jumps in the time that has passed are signalled, unlike time passing.
+        let mut seeked = player
+            .receive_signal("Seeked")
+            .await
+            .expect("Can listen for seeks");
+        let mut timer: TimerData = ron::from_str(
+            "(duration: (secs: 30, nanos: 0), end_after: (secs: 60, nanos: 0), paused: true)",
+        )
+        .expect("Valid timer");
+        let mut seek_to = async |timer: &TimerData| -> i64 {
+            sender.send_replace(Some(Track::of(TimerId(3), "Tea".into(), timer)));
+            seeked
+                .next()
+                .await
+                .expect("Seek is signalled")
+                .body()
+                .deserialize()
+                .expect("Seek has a position")
+        };
+        assert_eq!(seek_to(&timer).await, 30_000_000);
+        timer.reset();
+        assert_eq!(seek_to(&timer).await, 0);
+
+        // Without a timer there is nothing to control.
+        sender.send_replace(None);
+        player.call_method("Play", &()).await.expect("Can play");
+        let status: String = player
+            .get_property("PlaybackStatus")
+            .await
+            .expect("Has status");
+        assert_eq!(status, "Stopped");
+        assert!(commands.try_recv().is_err());
+
+        drop(sender);
+        serve.await.expect("Task did not panic").expect("Can serve");
+    }
+}
@@ -0,0 +1,137 @@
+//! Tells the user things the tray cannot show on its own, with desktop notifications.
+
+use std::collections::HashMap;
+
+use tokio::sync::mpsc::UnboundedReceiver;
+use zbus::{Connection, zvariant::Value};
+
+use super::tray_icon::APP_NAME;
+
+/// A message shown to the user by the desktop.
+#[derive(Clone, PartialEq, Debug)]
+pub(crate) struct Notification {
+    pub summary: String,
+    pub body: String,
+}
+
+/// Shows each notification the tray sends.
+///
+/// This runs until the tray stops sending notifications.
+pub(crate) async fn show(notifications: UnboundedReceiver<Notification>) {
+    let result = match Connection::session().await {
+        Ok(connection) => show_on(&connection, notifications).await,
+        Err(err) => Err(err),
+    };
+    if let Err(err) = result {
+        log::warn!("Unable to show notifications: {err}");
+    }
+}
+
+/// Shows each notification the tray sends with the notification server on the given bus.
+async fn show_on(
+    connection: &Connection,
+    mut notifications: UnboundedReceiver<Notification>,
+) -> zbus::Result<()> {
+    while let Some(Notification { summary, body }) = notifications.recv().await {
+        let hints: HashMap<&str, Value> = HashMap::new();
+        let result = connection
+            .call_method(
+                Some("org.freedesktop.Notifications"),
+                "/org/freedesktop/Notifications",
+                Some("org.freedesktop.Notifications"),
+                "Notify",
+                // The notification is new, without an icon or actions & expires when the server chooses.
+                &(
+                    APP_NAME,
+                    0u32,
+                    "",
+                    summary.as_str(),
+                    body.as_str(),
+                    Vec::<&str>::new(),
+                    hints,
+                    -1i32,
+                ),
+            )
+            .await;
+
+        // The next notification may still be shown, such as once a notification server starts.
+        if let Err(err) = result {
+            log::warn!("Unable to show notification '{summary}': {err}");
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, time::Duration};
+
+    use futures_util::TryStreamExt as _;
+    use tokio::sync::mpsc;
+    use zbus::{MessageStream, message, zvariant::OwnedValue};
+
+    use super::{Notification, show_on};
+    use crate::tray::test_bus::Bus;
+
+    /// The arguments of a call to show a notification.
+    #[derive(serde::Deserialize, zbus::zvariant::Type)]
+    struct Notify {
+        app_name: String,
+        _replaces_id: u32,
+        _app_icon: String,
+        summary: String,
+        body: String,
+        _actions: Vec<String>,
+        _hints: HashMap<String, OwnedValue>,
+        _expire_timeout: i32,
+    }
+
+    #[tokio::test]
+    async fn shows_notifications() {
+        let Some(bus) = Bus::start() else {
+            eprintln!("dbus-daemon is not installed, skipping");
+            return;
+        };
+
+        let server = bus.connect(Some("org.freedesktop.Notifications")).await;
+        let mut calls = MessageStream::from(&server);
+        let tray = bus.connect(None).await;
+
+        let (sender, receiver) = mpsc::unbounded_channel();
+        sender
+            .send(Notification {
+                summary: "Missed timers".into(),
+                body: "Tea finished at 09:05".into(),
+            })
+            .expect("Task is running");
+        drop(sender);
+
+        // A mock notification server, which answers the first notification it is asked to show.
+        let serve = async {
+            while let Some(call) = calls.try_next().await.expect("Can receive calls") {
+                let header = call.header();
+                if header.message_type() != message::Type::MethodCall
+                    || header.member().is_none_or(|member| member != "Notify")
+                {
+                    continue;
+                }
+
+                let notify: Notify = call.body().deserialize().expect("Valid notification");
+                server.reply(&header, &1u32).await.expect("Can reply");
+                return notify;
+            }
+            panic!("Bus closed before a notification was shown");
+        };
+
+        let (notify, shown) = tokio::time::timeout(Duration::from_secs(5), async {
+            tokio::join!(serve, show_on(&tray, receiver))
+        })
+        .await
+        .expect("Notifications are shown in time");
+        shown.expect("Can talk to the notification server");
+
+        assert_eq!(notify.app_name, "Gui Timer");
+        assert_eq!(notify.summary, "Missed timers");
+        assert_eq!(notify.body, "Tea finished at 09:05");
+    }
+}
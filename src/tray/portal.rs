@@ -0,0 +1,547 @@
+//! Binds the keyboard shortcuts through the XDG GlobalShortcuts portal, which works under Wayland as well as X11.
+//!
+//! Portal methods reply with a request object, which later sends the result as a `Response` signal.
+
+use std::{
+    collections::HashMap,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use futures_util::StreamExt as _;
+use tokio::sync::{mpsc::UnboundedSender, watch};
+use zbus::{
+    Connection,
+    zvariant::{ObjectPath, OwnedObjectPath, OwnedValue, Value},
+};
+
+use crate::shortcuts::{Shortcut, Shortcuts};
+
+#[zbus::proxy(
+    interface = "org.freedesktop.portal.GlobalShortcuts",
+    default_service = "org.freedesktop.portal.Desktop",
+    default_path = "/org/freedesktop/portal/desktop"
+)]
+trait GlobalShortcuts {
+    fn create_session(&self, options: HashMap<&str, Value<'_>>) -> zbus::Result<OwnedObjectPath>;
+
+    fn bind_shortcuts(
+        &self,
+        session_handle: &ObjectPath<'_>,
+        shortcuts: &[(&str, HashMap<&str, Value<'_>>)],
+        parent_window: &str,
+        options: HashMap<&str, Value<'_>>,
+    ) -> zbus::Result<OwnedObjectPath>;
+
+    #[zbus(signal)]
+    fn activated(
+        &self,
+        session_handle: ObjectPath<'_>,
+        shortcut_id: &str,
+        timestamp: u64,
+        options: HashMap<String, OwnedValue>,
+    ) -> zbus::Result<()>;
+}
+
+#[zbus::proxy(
+    interface = "org.freedesktop.portal.Request",
+    default_service = "org.freedesktop.portal.Desktop"
+)]
+trait Request {
+    #[zbus(signal)]
+    fn response(&self, response: u32, results: HashMap<String, OwnedValue>) -> zbus::Result<()>;
+}
+
+#[zbus::proxy(
+    interface = "org.freedesktop.portal.Session",
+    default_service = "org.freedesktop.portal.Desktop"
+)]
+trait Session {
+    fn close(&self) -> zbus::Result<()>;
+}
+
+/// Binds the keyboard shortcuts, sending each one the user presses.
+///
+/// The shortcuts are bound again whenever the user changes them, until the tray stops sending them.
+pub(crate) async fn bind(shortcuts: watch::Receiver<Shortcuts>, sender: UnboundedSender<Shortcut>) {
+    let result = match Connection::session().await {
+        Ok(connection) => bind_on(&connection, shortcuts, sender).await,
+        Err(err) => Err(err),
+    };
+    if let Err(err) = result {
+        log::warn!("Unable to bind keyboard shortcuts: {err}");
+    }
+}
+
+/// Binds the keyboard shortcuts with the portal on the given bus, sending each one the user presses.
+///
+/// If the shortcuts cannot be bound, such as when the user cancels the desktop's dialog, they are tried again once
+/// the user changes them.
+async fn bind_on(
+    connection: &Connection,
+    mut shortcuts: watch::Receiver<Shortcuts>,
+    sender: UnboundedSender<Shortcut>,
+) -> zbus::Result<()> {
+    let portal = GlobalShortcutsProxy::new(connection).await?;
+    let mut activated = portal.receive_activated().await?;
+
+    loop {
+        let bindings = shortcuts.borrow_and_update().clone();
+        let session = match bind_session(connection, &portal, &bindings).await {
+            Ok(session) => {
+                log::info!("Bound keyboard shortcuts");
+                Some(session)
+            }
+            Err(err) => {
+                log::warn!("Unable to bind keyboard shortcuts, until they are changed: {err}");
+                None
+            }
+        };
+
+        loop {
+            tokio::select! {
+                changed = shortcuts.changed() => {
+                    // A session can only bind its shortcuts once, so a new session is created for the changes.
+                    if let Some(session) = &session
+                        && let Err(err) = close_session(connection, session).await
+                    {
+                        log::debug!("Unable to close the previous shortcuts session: {err}");
+                    }
+
+                    match changed {
+                        Ok(()) => break,
+                        Err(_) => return Ok(()),
+                    }
+                }
+                Some(signal) = activated.next() => {
+                    let Some(session) = &session else {
+                        continue;
+                    };
+                    let args = match signal.args() {
+                        Ok(args) => args,
+                        Err(err) => {
+                            log::warn!("Invalid keyboard shortcut from the portal: {err}");
+                            continue;
+                        }
+                    };
+                    if *args.session_handle() != session.as_ref() {
+                        continue;
+                    }
+                    if let Some(shortcut) = Shortcut::from_id(args.shortcut_id())
+                        && sender.send(shortcut).is_err()
+                    {
+                        return Ok(());
+                    }
+                }
+                else => return Ok(()),
+            }
+        }
+    }
+}
+
+/// Binds the shortcuts to a new session, returning its handle.
+async fn bind_session(
+    connection: &Connection,
+    portal: &GlobalShortcutsProxy<'_>,
+    bindings: &Shortcuts,
+) -> zbus::Result<OwnedObjectPath> {
+    let session = create_session(connection, portal).await?;
+    if let Err(err) = bind_shortcuts(connection, portal, &session, bindings).await {
+        let _ = close_session(connection, &session).await;
+        return Err(err);
+    }
+    Ok(session)
+}
+
+/// Ends a session, unbinding its shortcuts.
+async fn close_session(connection: &Connection, session: &ObjectPath<'_>) -> zbus::Result<()> {
+    SessionProxy::builder(connection)
+        .path(session)?
+        .build()
+        .await?
+        .close()
+        .await
+}
+
+/// Starts a session that the shortcuts are bound to, returning its handle.
+async fn create_session(
+    connection: &Connection,
+    portal: &GlobalShortcutsProxy<'_>,
+) -> zbus::Result<OwnedObjectPath> {
+    let session_token = token();
+    let results = request(connection, |handle_token| async move {
+        let options = HashMap::from([
+            ("handle_token", Value::from(handle_token.as_str())),
+            ("session_handle_token", Value::from(session_token.as_str())),
+        ]);
+        portal.create_session(options).await
+    })
+    .await?;
+
+    // Older portals send the handle as a string rather than an object path.
+    let handle = results
+        .get("session_handle")
+        .ok_or_else(|| zbus::Error::Failure("The portal did not return a session".into()))?;
+    match handle.downcast_ref::<ObjectPath>() {
+        Ok(path) => Ok(path.into()),
+        Err(_) => Ok(OwnedObjectPath::try_from(handle.downcast_ref::<String>()?)?),
+    }
+}
+
+/// Asks the portal to bind the shortcuts to the session.
+async fn bind_shortcuts(
+    connection: &Connection,
+    portal: &GlobalShortcutsProxy<'_>,
+    session: &ObjectPath<'_>,
+    bindings: &Shortcuts,
+) -> zbus::Result<()> {
+    let shortcuts: Vec<_> = Shortcut::ALL
+        .into_iter()
+        .map(|shortcut| {
+            let properties = HashMap::from([
+                ("description", Value::from(shortcut.description())),
+                ("preferred_trigger", Value::from(bindings.trigger(shortcut))),
+            ]);
+            (shortcut.id(), properties)
+        })
+        .collect();
+
+    request(connection, |handle_token| async move {
+        let options = HashMap::from([("handle_token", Value::from(handle_token.as_str()))]);
+        portal
+            .bind_shortcuts(session, &shortcuts, "", options)
+            .await
+    })
+    .await?;
+
+    Ok(())
+}
+
+/// Calls a portal method with a new handle token, returning the results sent to its request object.
+async fn request<F: Future<Output = zbus::Result<OwnedObjectPath>>>(
+    connection: &Connection,
+    call: impl FnOnce(String) -> F,
+) -> zbus::Result<HashMap<String, OwnedValue>> {
+    let handle_token = token();
+
+    // The response may be sent before the method returns, so it is listened for beforehand.
+    let sender = connection
+        .unique_name()
+        .ok_or_else(|| zbus::Error::Failure("Not connected to the bus".into()))?
+        .trim_start_matches(':')
+        .replace('.', "_");
+    let path = format!("/org/freedesktop/portal/desktop/request/{sender}/{handle_token}");
+    let request = RequestProxy::builder(connection)
+        .path(path)?
+        .build()
+        .await?;
+    let mut responses = request.receive_response().await?;
+
+    call(handle_token).await?;
+
+    let response = responses
+        .next()
+        .await
+        .ok_or_else(|| zbus::Error::Failure("The portal did not respond".into()))?;
+    let args = response.args()?;
+    match args.response() {
+        0 => Ok(args.results().clone()),
+        1 => Err(zbus::Error::Failure(
+            "The user cancelled the request".into(),
+        )),
+        _ => Err(zbus::Error::Failure(
+            "The portal was unable to complete the request".into(),
+        )),
+    }
+}
+
+/// A unique token for naming requests & sessions.
+fn token() -> String {
+    static NEXT: AtomicU32 = AtomicU32::new(0);
+    format!(
+        "{}_{}",
+        env!("CARGO_PKG_NAME"),
+        NEXT.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, time::Duration};
+
+    use tokio::sync::{mpsc, watch};
+    use zbus::{
+        Connection,
+        message::Header,
+        object_server::SignalEmitter,
+        zvariant::{ObjectPath, OwnedObjectPath, OwnedValue, Value},
+    };
+
+    use super::bind_on;
+    use crate::{
+        shortcuts::{Shortcut, Shortcuts},
+        tray::test_bus::Bus,
+    };
+
+    const PORTAL_PATH: &str = "/org/freedesktop/portal/desktop";
+
+    /// A mock of the desktop's portal, which binds every shortcut with its preferred trigger.
+    #[derive(Default)]
+    struct MockPortal {
+        /// The session each set of shortcuts was bound to, along with each shortcut's id & preferred trigger.
+        bound: Vec<(OwnedObjectPath, Vec<(String, String)>)>,
+        /// How many more times the user cancels binding the shortcuts.
+        cancels: usize,
+        /// How many times the user cancelled binding the shortcuts.
+        cancelled: usize,
+    }
+
+    impl MockPortal {
+        /// The path of a request or session object for the caller.
+        fn path(header: &Header<'_>, kind: &str, token: &OwnedValue) -> zbus::fdo::Result<String> {
+            let sender = header
+                .sender()
+                .ok_or_else(|| zbus::fdo::Error::Failed("No sender".into()))?
+                .trim_start_matches(':')
+                .replace('.', "_");
+            let token = token.downcast_ref::<String>().map_err(zbus::Error::from)?;
+            Ok(format!("{PORTAL_PATH}/{kind}/{sender}/{token}"))
+        }
+
+        /// Sends the response & results of a request to its request object.
+        async fn respond(
+            connection: &Connection,
+            request: &str,
+            response: u32,
+            results: HashMap<&str, Value<'_>>,
+        ) -> zbus::fdo::Result<OwnedObjectPath> {
+            connection
+                .emit_signal(
+                    None::<()>,
+                    request,
+                    "org.freedesktop.portal.Request",
+                    "Response",
+                    &(response, results),
+                )
+                .await?;
+            Ok(OwnedObjectPath::try_from(request.to_owned()).map_err(zbus::Error::from)?)
+        }
+    }
+
+    #[zbus::interface(name = "org.freedesktop.portal.GlobalShortcuts")]
+    impl MockPortal {
+        async fn create_session(
+            &self,
+            #[zbus(header)] header: Header<'_>,
+            #[zbus(connection)] connection: &Connection,
+            options: HashMap<String, OwnedValue>,
+        ) -> zbus::fdo::Result<OwnedObjectPath> {
+            let request = Self::path(&header, "request", &options["handle_token"])?;
+            let session = Self::path(&header, "session", &options["session_handle_token"])?;
+            connection
+                .object_server()
+                .at(session.as_str(), MockSession)
+                .await?;
+
+            let results = HashMap::from([("session_handle", Value::from(session.as_str()))]);
+            Self::respond(connection, &request, 0, results).await
+        }
+
+        async fn bind_shortcuts(
+            &mut self,
+            #[zbus(header)] header: Header<'_>,
+            #[zbus(connection)] connection: &Connection,
+            session_handle: OwnedObjectPath,
+            shortcuts: Vec<(String, HashMap<String, OwnedValue>)>,
+            _parent_window: String,
+            options: HashMap<String, OwnedValue>,
+        ) -> zbus::fdo::Result<OwnedObjectPath> {
+            let request = Self::path(&header, "request", &options["handle_token"])?;
+            if self.cancels > 0 {
+                self.cancels -= 1;
+                self.cancelled += 1;
+                return Self::respond(connection, &request, 1, HashMap::new()).await;
+            }
+
+            let triggers = shortcuts
+                .iter()
+                .map(|(id, properties)| {
+                    let trigger = properties["preferred_trigger"]
+                        .downcast_ref::<String>()
+                        .map_err(zbus::Error::from)?;
+                    Ok((id.clone(), trigger))
+                })
+                .collect::<zbus::fdo::Result<_>>()?;
+            self.bound.push((session_handle, triggers));
+
+            Self::respond(connection, &request, 0, HashMap::new()).await
+        }
+    }
+
+    /// A mock of a session created by the portal.
+    struct MockSession;
+
+    #[zbus::interface(name = "org.freedesktop.portal.Session")]
+    impl MockSession {
+        async fn close(
+            &self,
+            #[zbus(connection)] connection: &Connection,
+            #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        ) -> zbus::fdo::Result<()> {
+            connection
+                .object_server()
+                .remove::<Self, _>(emitter.path())
+                .await?;
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn binds_shortcuts() {
+        let Some(bus) = Bus::start() else {
+            eprintln!("dbus-daemon is not installed, skipping");
+            return;
+        };
+
+        let desktop = bus.connect(Some("org.freedesktop.portal.Desktop")).await;
+        desktop
+            .object_server()
+            .at(PORTAL_PATH, MockPortal::default())
+            .await
+            .expect("Can serve mock");
+        let portal = desktop
+            .object_server()
+            .interface::<_, MockPortal>(PORTAL_PATH)
+            .await
+            .expect("Mock is served");
+
+        let tray = bus.connect(None).await;
+        let (sender, receiver) = watch::channel(Shortcuts::default());
+        let (pressed, mut shortcuts) = mpsc::unbounded_channel();
+        let bind = tokio::spawn(async move { bind_on(&tray, receiver, pressed).await });
+
+        // Waits for the portal to have bound the number of sets of shortcuts, returning the latest session.
+        let bound = |count: usize| {
+            let portal = portal.clone();
+            async move {
+                for _ in 0..100 {
+                    let portal = portal.get().await;
+                    if portal.bound.len() == count {
+                        return portal.bound[count - 1].clone();
+                    }
+                    drop(portal);
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                }
+                panic!("Shortcuts were not bound");
+            }
+        };
+
+        let (session, triggers) = bound(1).await;
+        assert!(triggers.contains(&("toggle-gui".into(), "CTRL+ALT+g".into())));
+        assert_eq!(triggers.len(), Shortcut::ALL.len());
+
+        // The desktop tells the tray when a shortcut is pressed.
+        let press = |session: OwnedObjectPath, id: &'static str| {
+            let desktop = desktop.clone();
+            async move {
+                desktop
+                    .emit_signal(
+                        None::<()>,
+                        PORTAL_PATH,
+                        "org.freedesktop.portal.GlobalShortcuts",
+                        "Activated",
+                        &(
+                            ObjectPath::from(&session),
+                            id,
+                            0u64,
+                            HashMap::<&str, Value>::new(),
+                        ),
+                    )
+                    .await
+                    .expect("Can emit signal");
+            }
+        };
+        press(session.clone(), "toggle-gui").await;
+        assert_eq!(shortcuts.recv().await, Some(Shortcut::ToggleGui));
+
+        // Changing the shortcuts binds them to a new session, & the old session is ignored.
+        sender.send_replace(Shortcuts {
+            pause_all: "CTRL+SHIFT+space".into(),
+            ..Shortcuts::default()
+        });
+        let (new_session, triggers) = bound(2).await;
+        assert!(triggers.contains(&("pause-all".into(), "CTRL+SHIFT+space".into())));
+        assert_ne!(new_session, session);
+
+        press(session, "start-timer").await;
+        press(new_session, "pause-all").await;
+        assert_eq!(shortcuts.recv().await, Some(Shortcut::PauseAll));
+
+        drop(sender);
+        bind.await
+            .expect("Task did not panic")
+            .expect("Can talk to portal");
+    }
+
+    #[tokio::test]
+    async fn retries_after_cancelling() {
+        let Some(bus) = Bus::start() else {
+            eprintln!("dbus-daemon is not installed, skipping");
+            return;
+        };
+
+        let desktop = bus.connect(Some("org.freedesktop.portal.Desktop")).await;
+        let mock = MockPortal {
+            cancels: 1,
+            ..MockPortal::default()
+        };
+        desktop
+            .object_server()
+            .at(PORTAL_PATH, mock)
+            .await
+            .expect("Can serve mock");
+        let portal = desktop
+            .object_server()
+            .interface::<_, MockPortal>(PORTAL_PATH)
+            .await
+            .expect("Mock is served");
+
+        let tray = bus.connect(None).await;
+        let (sender, receiver) = watch::channel(Shortcuts::default());
+        let bind =
+            tokio::spawn(
+                async move { bind_on(&tray, receiver, mpsc::unbounded_channel().0).await },
+            );
+
+        for _ in 0..100 {
+            if portal.get().await.cancelled == 1 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        assert_eq!(portal.get().await.cancelled, 1);
+        assert!(!bind.is_finished());
+
+        // The user changing the shortcuts tries again.
+        sender.send_replace(Shortcuts {
+            pause_all: "CTRL+SHIFT+space".into(),
+            ..Shortcuts::default()
+        });
+        let mut bound = Vec::new();
+        for _ in 0..100 {
+            bound.clone_from(&portal.get().await.bound);
+            if !bound.is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        let [(_, triggers)] = bound.as_slice() else {
+            panic!("Shortcuts were bound once, not {} times", bound.len());
+        };
+        assert!(triggers.contains(&("pause-all".into(), "CTRL+SHIFT+space".into())));
+
+        drop(sender);
+        bind.await
+            .expect("Task did not panic")
+            .expect("Can talk to portal");
+    }
+}
@@ -0,0 +1,360 @@
+//! Notices when the user leaves their computer, so timers can be paused whilst they are away.
+//!
+//! The user is away whilst the screen saver is active, the session is locked, or logind reports the session as idle.
+
+use std::{collections::BTreeSet, time::Instant};
+
+use futures_util::StreamExt as _;
+use tokio::sync::mpsc::{self, UnboundedSender};
+use zbus::Connection;
+
+use crate::comms::timer_sync::{SyncMessage, TimerCommand, TimerId, Timers};
+
+/// Whether the user is at their computer.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) enum Presence {
+    Away,
+    Present,
+}
+
+impl Presence {
+    fn away(away: bool) -> Self {
+        match away {
+            true => Self::Away,
+            false => Self::Present,
+        }
+    }
+}
+
+/// What noticed the user leaving or returning.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+enum Source {
+    ScreenSaver,
+    Lock,
+    Idle,
+}
+
+/// Combines what each [`Source`] says, so the user is only present once none of them say the user is away.
+#[derive(Default)]
+struct Sources {
+    /// The sources that say the user is away.
+    away: BTreeSet<Source>,
+}
+
+impl Sources {
+    /// Records whether the source says the user is away, returning the user's presence if it changed.
+    fn update(&mut self, source: Source, away: bool) -> Option<Presence> {
+        let was_away = !self.away.is_empty();
+        match away {
+            true => self.away.insert(source),
+            false => self.away.remove(&source),
+        };
+
+        let is_away = !self.away.is_empty();
+        (was_away != is_away).then_some(Presence::away(is_away))
+    }
+}
+
+#[zbus::proxy(
+    interface = "org.freedesktop.ScreenSaver",
+    default_service = "org.freedesktop.ScreenSaver",
+    default_path = "/org/freedesktop/ScreenSaver"
+)]
+trait ScreenSaver {
+    #[zbus(signal)]
+    fn active_changed(&self, active: bool) -> zbus::Result<()>;
+}
+
+#[zbus::proxy(
+    interface = "org.freedesktop.login1.Session",
+    default_service = "org.freedesktop.login1",
+    default_path = "/org/freedesktop/login1/session/auto"
+)]
+trait LoginSession {
+    #[zbus(signal)]
+    fn lock(&self) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    fn unlock(&self) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn idle_hint(&self) -> zbus::Result<bool>;
+}
+
+/// Sends the user's presence whenever they leave or return to their computer.
+///
+/// This runs until the buses it listens on are unavailable.
+pub(crate) async fn watch(sender: UnboundedSender<Presence>) {
+    let (report, mut reports) = mpsc::unbounded_channel();
+
+    let watchers = async move {
+        let screen_saver = async {
+            let result = match Connection::session().await {
+                Ok(connection) => watch_screen_saver(&connection, &report).await,
+                Err(err) => Err(err),
+            };
+            if let Err(err) = result {
+                log::warn!("Unable to watch the screen saver: {err}");
+            }
+        };
+
+        let login_session = async {
+            let result = match Connection::system().await {
+                Ok(connection) => watch_login_session(&connection, &report).await,
+                Err(err) => Err(err),
+            };
+            if let Err(err) = result {
+                log::warn!("Unable to watch the login session: {err}");
+            }
+        };
+
+        tokio::join!(screen_saver, login_session);
+    };
+
+    let combine = async move {
+        let mut sources = Sources::default();
+        while let Some((source, away)) = reports.recv().await {
+            if let Some(presence) = sources.update(source, away)
+                && sender.send(presence).is_err()
+            {
+                break;
+            }
+        }
+    };
+
+    tokio::join!(watchers, combine);
+}
+
+/// Reports whether the user is away whenever the screen saver starts or stops.
+async fn watch_screen_saver(
+    connection: &Connection,
+    report: &UnboundedSender<(Source, bool)>,
+) -> zbus::Result<()> {
+    let proxy = ScreenSaverProxy::new(connection).await?;
+    let mut changes = proxy.receive_active_changed().await?;
+
+    while let Some(change) = changes.next().await {
+        let active = change.args()?.active;
+        if report.send((Source::ScreenSaver, active)).is_err() {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Reports whether the user is away whenever the session is locked, unlocked, or becomes idle.
+async fn watch_login_session(
+    connection: &Connection,
+    report: &UnboundedSender<(Source, bool)>,
+) -> zbus::Result<()> {
+    let proxy = LoginSessionProxy::new(connection).await?;
+    let locks = proxy.receive_lock().await?.map(|_| (Source::Lock, true));
+    let unlocks = proxy.receive_unlock().await?.map(|_| (Source::Lock, false));
+    let idle = proxy
+        .receive_idle_hint_changed()
+        .await
+        .then(|change| async move { change.get().await.ok().map(|idle| (Source::Idle, idle)) })
+        .filter_map(|change| async move { change });
+
+    let mut changes = std::pin::pin!(futures_util::stream::select(
+        futures_util::stream::select(locks, unlocks),
+        idle
+    ));
+    while let Some(change) = changes.next().await {
+        if report.send(change).is_err() {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Pauses the timers that opted in whilst the user is away, resuming them when the user returns.
+///
+/// Timers that keep running record how long the user was away for.
+#[derive(Default)]
+pub(crate) struct AutoPause {
+    /// When the user left, if they are away.
+    away_since: Option<Instant>,
+    /// The timers that were paused because the user left.
+    paused: Vec<TimerId>,
+}
+
+impl AutoPause {
+    /// Handles a change in the user's presence at the given time, returning the changes to send to clients.
+    pub fn update(
+        &mut self,
+        presence: Presence,
+        timers: &mut Timers,
+        now: Instant,
+    ) -> Vec<SyncMessage> {
+        match presence {
+            Presence::Away => self.away(timers, now),
+            Presence::Present => self.present(timers, now),
+        }
+    }
+
+    fn away(&mut self, timers: &mut Timers, now: Instant) -> Vec<SyncMessage> {
+        // The screen saver & lock screen often start together.
+        if self.away_since.is_some() {
+            return Vec::new();
+        }
+        self.away_since = Some(now);
+
+        self.paused = timers
+            .iter()
+            .filter(|(_, timer)| timer.pause_when_away() && !timer.paused() && !timer.finished())
+            .map(|(id, _)| id)
+            .collect();
+
+        self.paused
+            .iter()
+            .filter_map(|id| timers.apply(TimerCommand::Pause(*id, true)))
+            .collect()
+    }
+
+    fn present(&mut self, timers: &mut Timers, now: Instant) -> Vec<SyncMessage> {
+        let Some(away_since) = self.away_since.take() else {
+            return Vec::new();
+        };
+        let away = now.saturating_duration_since(away_since);
+        let paused = std::mem::take(&mut self.paused);
+
+        let running: Vec<_> = timers
+            .iter()
+            .filter(|(_, timer)| !timer.paused())
+            .map(|(id, _)| id)
+            .collect();
+
+        let mut messages = Vec::new();
+        for id in running {
+            messages.extend(timers.update(id, |timer| timer.record_away(away)));
+        }
+        for id in paused {
+            messages.extend(timers.apply(TimerCommand::Pause(id, false)));
+        }
+        messages
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use tokio::sync::mpsc;
+
+    use super::{AutoPause, Presence, Source, Sources, watch_screen_saver};
+    use crate::{
+        comms::timer_sync::{TimerCommand, TimerId, Timers},
+        tray::test_bus::Bus,
+    };
+
+    #[tokio::test]
+    async fn screen_saver_signals() {
+        let Some(bus) = Bus::start() else {
+            eprintln!("dbus-daemon is not installed, skipping");
+            return;
+        };
+
+        // A mock of the desktop's screen saver.
+        let screen_saver = bus.connect(Some("org.freedesktop.ScreenSaver")).await;
+        let tray = bus.connect(None).await;
+
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let watch = tokio::spawn(async move { watch_screen_saver(&tray, &sender).await });
+
+        for active in [true, false] {
+            // The watcher may not have subscribed yet, so the signal is sent until it is received.
+            let received = loop {
+                screen_saver
+                    .emit_signal(
+                        None::<()>,
+                        "/org/freedesktop/ScreenSaver",
+                        "org.freedesktop.ScreenSaver",
+                        "ActiveChanged",
+                        &(active,),
+                    )
+                    .await
+                    .expect("Can emit signal");
+
+                if let Ok(received) =
+                    tokio::time::timeout(Duration::from_millis(100), receiver.recv()).await
+                {
+                    break received;
+                }
+            };
+            assert_eq!(received, Some((Source::ScreenSaver, active)));
+
+            // Drain any duplicates sent whilst waiting.
+            while receiver.try_recv().is_ok() {}
+        }
+
+        watch.abort();
+    }
+
+    #[test]
+    fn away_whilst_any_source_is() {
+        let mut sources = Sources::default();
+
+        assert_eq!(
+            sources.update(Source::ScreenSaver, true),
+            Some(Presence::Away)
+        );
+        assert_eq!(sources.update(Source::Lock, true), None);
+
+        // The screen saver stopping does not unlock the session.
+        assert_eq!(sources.update(Source::ScreenSaver, false), None);
+        assert_eq!(sources.update(Source::Lock, false), Some(Presence::Present));
+
+        // Sources reporting the user is present when they already are changes nothing.
+        assert_eq!(sources.update(Source::Idle, false), None);
+        assert_eq!(sources.update(Source::Idle, true), Some(Presence::Away));
+        assert_eq!(sources.update(Source::Idle, true), None);
+        assert_eq!(sources.update(Source::Idle, false), Some(Presence::Present));
+    }
+
+    #[test]
+    fn pauses_opted_in_timers() {
+        let mut timers = Timers::default();
+        let _ = timers.apply(TimerCommand::Create(Duration::from_secs(60)));
+        let _ = timers.apply(TimerCommand::Create(Duration::from_secs(60)));
+        let _ = timers.apply(TimerCommand::PauseWhenAway(TimerId(0), true));
+
+        let mut auto_pause = AutoPause::default();
+        let left = Instant::now();
+
+        let messages = auto_pause.update(Presence::Away, &mut timers, left);
+        assert_eq!(messages.len(), 1);
+        assert!(
+            auto_pause
+                .update(Presence::Away, &mut timers, left)
+                .is_empty()
+        );
+
+        let paused = |timers: &Timers| {
+            timers
+                .iter()
+                .map(|(_, timer)| timer.paused())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(paused(&timers), [true, false]);
+
+        let messages = auto_pause.update(
+            Presence::Present,
+            &mut timers,
+            left + Duration::from_secs(30),
+        );
+        assert_eq!(messages.len(), 2);
+        assert_eq!(paused(&timers), [false, false]);
+        assert!(
+            auto_pause
+                .update(Presence::Present, &mut timers, left)
+                .is_empty()
+        );
+
+        // The timer that kept running records that the user was away, up to the time that has passed.
+        let (_, timer) = timers.iter().nth(1).expect("Timer was created");
+        assert_eq!(timer.away(), timer.duration());
+    }
+}
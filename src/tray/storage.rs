@@ -0,0 +1,107 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    comms::timer_sync::{TimerId, Timers},
+    dnd::DoNotDisturb,
+    format::TimeFormat,
+    shortcuts::Shortcuts,
+    timer::Preset,
+};
+
+/// Data kept by the tray between runs.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub(crate) struct Persistent {
+    pub timers: Timers,
+    pub presets: Vec<Preset>,
+    /// The timer shown by the tray icon, regardless of the other timers.
+    pub pinned: Option<TimerId>,
+    pub dnd: DoNotDisturb,
+    pub shortcuts: Shortcuts,
+    /// How times are shown, unless a timer has its own format.
+    pub time_format: TimeFormat,
+}
+
+impl Default for Persistent {
+    fn default() -> Self {
+        Self {
+            timers: Timers::default(),
+            presets: Preset::defaults(),
+            pinned: None,
+            dnd: DoNotDisturb::default(),
+            shortcuts: Shortcuts::default(),
+            time_format: TimeFormat::default(),
+        }
+    }
+}
+
+/// The file the state of the tray is saved to.
+#[cfg(not(test))]
+fn state_path() -> Option<PathBuf> {
+    eframe::storage_dir(env!("CARGO_PKG_NAME")).map(|dir| dir.join("tray.ron"))
+}
+
+/// Tests never save, so the user's own state is left alone.
+#[cfg(test)]
+fn state_path() -> Option<PathBuf> {
+    None
+}
+
+/// Loads the data saved by a previous tray.
+///
+/// If there is no saved data, or it cannot be loaded, then the defaults are used.
+pub(crate) fn load() -> Persistent {
+    let mut persistent = read().unwrap_or_default();
+
+    // Running timers count the time the tray was not running for.
+    persistent.timers.tick();
+    persistent
+}
+
+/// Reads the saved data, if there is any & it can be loaded.
+pub(crate) fn read() -> Option<Persistent> {
+    let path = state_path()?;
+
+    let state = match std::fs::read_to_string(&path) {
+        Ok(state) => state,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return None,
+        Err(err) => {
+            log::error!("Unable to read tray state from '{}': {err}", path.display());
+            return None;
+        }
+    };
+
+    ron::from_str(&state)
+        .inspect_err(|err| log::error!("Invalid tray state in '{}': {err}", path.display()))
+        .ok()
+}
+
+/// Saves the data so it can be loaded by the next tray.
+pub(crate) fn save(persistent: &mut Persistent) {
+    let Some(path) = state_path() else {
+        log::error!("Unable to find a directory to save tray state to.");
+        return;
+    };
+
+    persistent.timers.tick();
+    let state = match ron::ser::to_string_pretty(persistent, ron::ser::PrettyConfig::default()) {
+        Ok(state) => state,
+        Err(err) => {
+            log::error!("Unable to serialise tray state: {err}");
+            return;
+        }
+    };
+
+    if let Some(dir) = path.parent()
+        && let Err(err) = std::fs::create_dir_all(dir)
+    {
+        log::error!("Unable to create '{}': {err}", dir.display());
+        return;
+    }
+
+    if let Err(err) = std::fs::write(&path, state) {
+        log::error!("Unable to save tray state to '{}': {err}", path.display());
+    }
+}
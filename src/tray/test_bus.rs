@@ -0,0 +1,51 @@
+//! A private D-Bus bus for testing against mock services.
+
+use std::{
+    io::{BufRead as _, BufReader},
+    process::{Child, Command, Stdio},
+};
+
+/// A private session bus, which is stopped when dropped.
+pub(super) struct Bus {
+    daemon: Child,
+    address: String,
+}
+
+impl Bus {
+    /// Starts a bus, if `dbus-daemon` is installed.
+    pub fn start() -> Option<Self> {
+        let mut daemon = Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--print-address=1"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .ok()?;
+
+        let mut address = String::new();
+        BufReader::new(daemon.stdout.take()?)
+            .read_line(&mut address)
+            .ok()?;
+
+        Some(Self {
+            daemon,
+            address: address.trim().into(),
+        })
+    }
+
+    /// Connects to the bus, taking the given well-known name.
+    pub async fn connect(&self, name: Option<&str>) -> zbus::Connection {
+        let mut builder =
+            zbus::connection::Builder::address(self.address.as_str()).expect("Valid address");
+        if let Some(name) = name {
+            builder = builder.name(name).expect("Valid name");
+        }
+        builder.build().await.expect("Can connect to bus")
+    }
+}
+
+impl Drop for Bus {
+    fn drop(&mut self) {
+        let _ = self.daemon.kill();
+        let _ = self.daemon.wait();
+    }
+}
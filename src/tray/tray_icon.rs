@@ -1,52 +1,737 @@
 use crate::{
-    comms::{GuiAction, GuiResponse},
+    clock::LocalTime,
+    comms::{
+        GuiAction, GuiResponse,
+        timer_sync::{SyncMessage, TimerCommand, TimerId},
+    },
+    dnd::DoNotDisturb,
+    format::TimeFormat,
+    shortcuts::{Shortcut, Shortcuts},
+    timer::{KeepAwake, Preset, TimerData},
     until_global_cancel,
 };
-use image::GenericImageView;
-use ksni::Handle;
-use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use std::{
+    collections::BTreeSet,
+    path::PathBuf,
+    process::Child,
+    time::{Duration, Instant, SystemTime},
+};
+use tokio::sync::{
+    broadcast,
+    mpsc::{UnboundedReceiver, UnboundedSender},
+    watch,
+};
+use tokio::{
+    signal::unix::{SignalKind, signal},
+    time::MissedTickBehavior,
+};
+
+use super::{
+    GLOBAL_CANCEL, GuiLauncher, GuiState, TrayOptions,
+    comms::{ClientId, Outgoing},
+    handle::TrayHandle,
+    icon::{IconState, TrayIcon},
+    mpris::Track,
+    notify::Notification,
+    prompt_duration,
+    session::{AutoPause, Presence},
+    storage::{self, Persistent},
+};
+
+/// The name shown to the user for the tray.
+pub(super) const APP_NAME: &str = "Gui Timer";
+
+/// How often the tray refreshes the time remaining on each timer.
+const TICK_INTERVAL: Duration = Duration::from_secs(1);
 
-use super::{GLOBAL_CANCEL, GuiState, spawn_gui};
+/// How much time is added to or removed from a timer for each step scrolled over the tray icon.
+const SCROLL_STEP: Duration = Duration::from_secs(60);
+
+/// How long before a timer ends the computer is kept awake, when chosen from the tray.
+const KEEP_AWAKE_WINDOW: Duration = Duration::from_secs(5 * 60);
+
+/// How long a GUI has to connect to the tray after it is spawned, before it is killed.
+const OPEN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long a GUI has to close after it is asked to, before it is killed.
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long the GUI must have been open to be restarted after it crashes.
+///
+/// This stops a GUI that crashes as soon as it opens from being restarted forever.
+const RESTART_UPTIME: Duration = Duration::from_secs(10);
 
 pub(crate) struct TimerTray {
-    sender: UnboundedSender<GuiAction>,
+    sender: broadcast::Sender<Outgoing>,
+    /// Used to act on responses that did not come from the GUI, such as timers started from a prompt.
+    responses: UnboundedSender<GuiResponse>,
+    launcher: GuiLauncher,
+    /// The GUI process spawned by the tray, until it is reaped.
+    gui: Option<Child>,
+    /// Whether to open the GUI again if it crashes.
+    restart_gui: bool,
 
     state: GuiState,
+    /// The GUIs that have opened & not yet closed.
+    open_guis: BTreeSet<ClientId>,
+    /// When the GUI entered its current state.
+    state_changed: Instant,
+    /// Whether the tray is waiting for the GUI to close before quitting.
+    quitting: bool,
+    /// The authoritative state of every timer, along with the other data saved by the tray.
+    persistent: Persistent,
+    /// A directory containing the user's own tray icons.
+    icons: Option<PathBuf>,
+    icon: TrayIcon,
+
+    /// The timers that were finished when they were last checked.
+    finished: BTreeSet<TimerId>,
+    /// Whether do not disturb was on when it was last checked.
+    quiet: bool,
+    /// The timers that finished whilst do not disturb was on, which the user has not been told of yet.
+    missed: Vec<Missed>,
+    /// The timers the user was told they missed, until they dismiss them from the menu.
+    missed_history: Vec<Missed>,
+    /// Pauses the timers that opted in whilst the user is away.
+    auto_pause: AutoPause,
+    /// Whether any timer needs the computer kept awake.
+    keep_awake: watch::Sender<bool>,
+    /// The timer controlled by media keys.
+    player: watch::Sender<Option<Track>>,
+    /// The keys the user would like to trigger each keyboard shortcut.
+    shortcuts: watch::Sender<Shortcuts>,
+    /// Messages shown to the user by the desktop.
+    notifications: UnboundedSender<Notification>,
+}
+
+/// The channels the tray talks to the tasks running alongside it through.
+#[derive(Clone)]
+pub(crate) struct TaskSenders {
+    pub keep_awake: watch::Sender<bool>,
+    pub player: watch::Sender<Option<Track>>,
+    pub shortcuts: watch::Sender<Shortcuts>,
+    pub notifications: UnboundedSender<Notification>,
+}
+
+/// A timer that finished whilst do not disturb was on.
+struct Missed {
+    id: TimerId,
+    /// The name of the timer when it finished, for once it has been removed.
+    name: String,
+    at: LocalTime,
 }
 
 impl TimerTray {
-    pub(crate) fn new(sender: UnboundedSender<GuiAction>) -> Self {
-        Self {
+    /// Creates the tray with the state loaded from [`storage`].
+    ///
+    /// The tasks running alongside the tray are only told of its state once it is [`start`](Self::start)ed.
+    pub(crate) fn new(
+        sender: broadcast::Sender<Outgoing>,
+        responses: UnboundedSender<GuiResponse>,
+        launcher: GuiLauncher,
+        tasks: TaskSenders,
+        persistent: Persistent,
+        options: &TrayOptions,
+    ) -> Self {
+        let TaskSenders {
+            keep_awake,
+            player,
+            shortcuts,
+            notifications,
+        } = tasks;
+        let mut tray = Self {
             sender,
-            state: GuiState::OpenRequested,
+            responses,
+            launcher,
+            gui: None,
+            restart_gui: options.restart_gui,
+            state: GuiState::Closed,
+            open_guis: BTreeSet::new(),
+            state_changed: Instant::now(),
+            quitting: false,
+            persistent,
+            icons: options.icons.clone(),
+            icon: TrayIcon::new(options.icons.as_deref()),
+            finished: BTreeSet::new(),
+            quiet: false,
+            missed: Vec::new(),
+            missed_history: Vec::new(),
+            auto_pause: AutoPause::default(),
+            keep_awake,
+            player,
+            shortcuts,
+            notifications,
+        };
+        tray.finished = tray.finished_timers().collect();
+        tray.quiet = tray.persistent.dnd.active();
+        tray
+    }
+
+    /// Tells the tasks running alongside the tray the state it started with.
+    pub(super) fn start(&mut self) {
+        self.refresh_icon();
+        self.check_keep_awake();
+        self.shortcuts
+            .send_replace(self.persistent.shortcuts.clone());
+    }
+
+    /// The ids of every timer that has finished.
+    fn finished_timers(&self) -> impl Iterator<Item = TimerId> {
+        self.persistent
+            .timers
+            .iter()
+            .filter(|(_, timer)| timer.finished())
+            .map(|(id, _)| id)
+    }
+
+    /// Records the timers that finished since they were last checked, if do not disturb is on.
+    fn record_finished(&mut self) {
+        let now = LocalTime::at(SystemTime::now());
+
+        if self.quiet {
+            let newly_finished = self
+                .persistent
+                .timers
+                .iter()
+                .enumerate()
+                .filter(|(_, (id, timer))| timer.finished() && !self.finished.contains(id))
+                .map(|(index, (id, timer))| Missed {
+                    id,
+                    name: timer_name(index, timer),
+                    at: now,
+                });
+            self.missed.extend(newly_finished);
         }
+
+        self.finished = self.finished_timers().collect();
+    }
+
+    /// Describes a timer that finished whilst do not disturb was on, by its current name if it still exists.
+    fn describe_missed(&self, missed: &Missed) -> String {
+        let name = self
+            .persistent
+            .timers
+            .iter()
+            .enumerate()
+            .find(|(_, (id, _))| *id == missed.id)
+            .map_or_else(
+                || missed.name.clone(),
+                |(index, (_, timer))| timer_name(index, timer),
+            );
+        format!("{name} finished at {}", missed.at)
+    }
+
+    /// Updates whether do not disturb is on, as it changes with the quiet hours.
+    ///
+    /// The user is shown the timers they missed once do not disturb ends, each only the once.
+    fn check_dnd(&mut self) {
+        let quiet = self.persistent.dnd.active();
+        if self.quiet && !quiet && !self.missed.is_empty() {
+            let missed = std::mem::take(&mut self.missed);
+            let body = missed
+                .iter()
+                .map(|missed| self.describe_missed(missed))
+                .collect::<Vec<_>>()
+                .join("\n");
+            let notification = Notification {
+                summary: format!("Missed whilst in do not disturb ({})", missed.len()),
+                body,
+            };
+            if self.notifications.send(notification).is_err() {
+                log::warn!("Unable to show the timers missed whilst in do not disturb");
+            }
+            self.missed_history.extend(missed);
+        }
+        self.quiet = quiet;
+    }
+
+    /// Changes when the user is not alerted that timers have finished.
+    fn set_dnd(&mut self, dnd: DoNotDisturb) {
+        self.persistent.dnd = dnd;
+        self.send(GuiAction::DoNotDisturb(self.persistent.dnd.clone()));
+        self.check_dnd();
+    }
+
+    /// Turns do not disturb on or off.
+    fn toggle_dnd(&mut self) {
+        let mut dnd = self.persistent.dnd.clone();
+        dnd.enabled = !dnd.enabled;
+        self.set_dnd(dnd);
+        storage::save(&mut self.persistent);
+    }
+
+    /// The timer shown by the tray icon.
+    ///
+    /// This is the pinned timer, otherwise the running timer that will end soonest, then any finished timer, then the
+    /// paused timer closest to ending.
+    fn displayed_timer(&self) -> Option<(TimerId, &TimerData)> {
+        let timers = &self.persistent.timers;
+
+        if let Some(pinned) = self.persistent.pinned
+            && let Some(timer) = timers.iter().find(|(id, _)| *id == pinned)
+        {
+            return Some(timer);
+        }
+
+        let soonest = |paused: bool| {
+            timers
+                .iter()
+                .filter(move |(_, timer)| timer.paused() == paused && !timer.finished())
+                .min_by_key(|(_, timer)| timer.remaining())
+        };
+
+        soonest(false)
+            .or_else(|| timers.iter().find(|(_, timer)| timer.finished()))
+            .or_else(|| soonest(true))
+    }
+
+    /// Redraws the tray icon if the timer it shows has changed, & updates the media player controlling it.
+    fn refresh_icon(&mut self) {
+        let (state, track) = match self.displayed_timer() {
+            Some((id, timer)) => {
+                let index = self
+                    .persistent
+                    .timers
+                    .iter()
+                    .position(|(other, _)| other == id)
+                    .unwrap_or_default();
+                let track = Track::of(id, timer_name(index, timer), timer);
+                (IconState::of(timer), Some(track))
+            }
+            None => (IconState::Idle, None),
+        };
+
+        self.icon.update(state);
+        self.player.send_replace(track);
+    }
+
+    /// Updates whether the computer is kept awake, as timers start, stop & near their end.
+    fn check_keep_awake(&self) {
+        let wanted = self
+            .persistent
+            .timers
+            .iter()
+            .any(|(_, timer)| timer.keeps_awake());
+        self.keep_awake
+            .send_if_modified(|keep_awake| std::mem::replace(keep_awake, wanted) != wanted);
+    }
+
+    /// Writes the tray's state to disk.
+    pub(super) fn save(&mut self) {
+        storage::save(&mut self.persistent);
+    }
+
+    /// Pins the timer to the tray icon, or unpins it if it was already pinned.
+    fn toggle_pin(&mut self, id: TimerId) {
+        self.persistent.pinned = match self.persistent.pinned {
+            Some(pinned) if pinned == id => None,
+            _ => Some(id),
+        };
+        self.refresh_icon();
+        storage::save(&mut self.persistent);
+    }
+
+    /// Sends the action to every connected GUI.
+    fn send(&self, action: GuiAction) {
+        // Sending only fails when no GUI is connected.
+        let _ = self.sender.send(Outgoing { to: None, action });
+    }
+
+    /// Sends the action to a single GUI.
+    fn reply(&self, client: ClientId, action: GuiAction) {
+        let _ = self.sender.send(Outgoing {
+            to: Some(client),
+            action,
+        });
+    }
+
+    /// Sends a change in the timers to every connected GUI.
+    fn publish(&self, message: SyncMessage) {
+        self.send(GuiAction::Sync(message));
+    }
+
+    /// Sends the state of every timer to the GUI.
+    fn resync(&mut self, client: ClientId) {
+        let snapshot = self.persistent.timers.snapshot();
+        self.reply(client, GuiAction::Sync(snapshot));
+    }
+
+    /// Performs a change to the timers requested by the user.
+    fn command(&mut self, command: TimerCommand) {
+        if let TimerCommand::Remove(id) = command
+            && self.persistent.pinned == Some(id)
+        {
+            self.persistent.pinned = None;
+        }
+
+        let removed_overrun = self.persistent.timers.removed_overrun();
+        if let Some(message) = self.persistent.timers.apply(command) {
+            self.publish(message);
+            let removed = self.persistent.timers.removed_overrun();
+            if removed != removed_overrun {
+                self.send(GuiAction::RemovedOverrun(removed));
+            }
+            self.record_finished();
+            self.refresh_icon();
+            self.check_keep_awake();
+            storage::save(&mut self.persistent);
+        }
+    }
+
+    /// Forgets how long every timer has counted past its end, including those that have been removed.
+    fn forget_overrun(&mut self) {
+        for message in self.persistent.timers.forget_overrun() {
+            self.publish(message);
+        }
+        self.send(GuiAction::RemovedOverrun(Duration::ZERO));
+        storage::save(&mut self.persistent);
+    }
+
+    /// Pauses or resumes the timers that opted in when the user leaves or returns to their computer.
+    fn set_presence(&mut self, presence: Presence) {
+        log::info!("User is {presence:?}");
+
+        let messages =
+            self.auto_pause
+                .update(presence, &mut self.persistent.timers, Instant::now());
+        if messages.is_empty() {
+            return;
+        }
+
+        for message in messages {
+            self.publish(message);
+        }
+        self.refresh_icon();
+        self.check_keep_awake();
+        storage::save(&mut self.persistent);
+    }
+
+    /// Replaces the presets that timers can be started from.
+    fn set_presets(&mut self, presets: Vec<Preset>) {
+        self.persistent.presets = presets;
+        self.send(GuiAction::Presets(self.persistent.presets.clone()));
+    }
+
+    /// Changes the keys the user would like to trigger each keyboard shortcut.
+    ///
+    /// The shortcuts are only bound again if they changed, as the desktop may ask the user to confirm them.
+    fn set_shortcuts(&mut self, shortcuts: Shortcuts) {
+        self.persistent.shortcuts = shortcuts;
+        self.send(GuiAction::Shortcuts(self.persistent.shortcuts.clone()));
+        self.shortcuts.send_if_modified(|bound| {
+            let changed = *bound != self.persistent.shortcuts;
+            if changed {
+                bound.clone_from(&self.persistent.shortcuts);
+            }
+            changed
+        });
+    }
+
+    /// Changes how times are shown, unless a timer has its own format.
+    fn set_time_format(&mut self, format: TimeFormat) {
+        self.persistent.time_format = format;
+        self.send(GuiAction::TimeFormat(format));
+    }
+
+    /// Loads the presets, settings & icons again, after the user edited them by hand.
+    fn reload(&mut self) {
+        log::info!("Reloading configuration");
+
+        match storage::read() {
+            Some(saved) => {
+                self.reload_from(saved);
+                storage::save(&mut self.persistent);
+            }
+            None => log::warn!("No saved configuration to reload, keeping the current one"),
+        }
+
+        self.icon = TrayIcon::new(self.icons.as_deref());
+        self.refresh_icon();
+    }
+
+    /// Uses the presets & settings from the saved data.
+    ///
+    /// The timers are left as they are, as the saved timers are older than the tray's own.
+    fn reload_from(&mut self, saved: Persistent) {
+        self.set_presets(saved.presets);
+        self.set_dnd(saved.dnd);
+        self.set_shortcuts(saved.shortcuts);
+        self.set_time_format(saved.time_format);
+    }
+
+    /// Performs the action of a keyboard shortcut the user pressed.
+    fn shortcut(&mut self, shortcut: Shortcut) {
+        log::debug!("Shortcut pressed: {shortcut:?}");
+
+        match shortcut {
+            Shortcut::StartTimer => match self.persistent.presets.first() {
+                Some(preset) => self.command(TimerCommand::Create(preset.end_after)),
+                None => self.start_custom(),
+            },
+            Shortcut::PauseAll => self.toggle_pause_all(),
+            Shortcut::ToggleGui => self.toggle_gui(),
+        }
+    }
+
+    /// Asks the user for the length of a new timer, then starts it.
+    fn start_custom(&mut self) {
+        let responses = self.responses.clone();
+        tokio::spawn(async move {
+            if let Some(end_after) = prompt_duration().await {
+                let _ = responses.send(GuiResponse::Timer(TimerCommand::Create(end_after)));
+            }
+        });
     }
 
     /// Opens or closes the GUI depending on the current state.
+    ///
+    /// Every open GUI is closed, including those the user opened themselves.
     fn toggle_gui(&mut self) {
         match self.state {
             GuiState::Opened => {
-                if let Err(err) = self.sender.send(GuiAction::Close) {
-                    log::error!("Internal tray communication was closed unexpectedly: {err}");
-                    GLOBAL_CANCEL.cancel();
+                self.send(GuiAction::Close);
+                self.set_state(GuiState::CloseRequested);
+            }
+            GuiState::Closed => self.open_gui(),
+            GuiState::OpenRequested | GuiState::CloseRequested => {}
+        }
+    }
+
+    /// Moves the GUI into the given state.
+    ///
+    /// If the tray is quitting, it does so once the GUI has closed.
+    fn set_state(&mut self, state: GuiState) {
+        self.state = state;
+        self.state_changed = Instant::now();
+
+        // Any GUI still connected is treated as a previous GUI from now on.
+        if state == GuiState::Closed {
+            self.open_guis.clear();
+        }
+
+        if self.quitting && state == GuiState::Closed {
+            GLOBAL_CANCEL.cancel();
+        }
+    }
+
+    /// Spawns a new GUI.
+    pub(super) fn open_gui(&mut self) {
+        match self.launcher.spawn() {
+            Ok(child) => {
+                // A GUI that closed normally may not have been reaped yet.
+                if let Some(previous) = self.gui.replace(child) {
+                    reap(previous);
                 }
+                self.set_state(GuiState::OpenRequested);
+            }
+            Err(err) => log::error!("Unable to open GUI: {err}"),
+        }
+    }
+
+    /// Kills the GUI process, if the tray spawned one.
+    fn kill_gui(&mut self) {
+        let Some(mut child) = self.gui.take() else {
+            return;
+        };
+
+        if let Err(err) = child.kill() {
+            log::error!("Unable to kill GUI: {err}");
+        }
+        reap(child);
+    }
+
+    /// Closes the GUI after it stopped without telling the tray, restarting it if configured to.
+    fn gui_lost(&mut self) {
+        let restart = self.restart_gui
+            && !self.quitting
+            && self.state == GuiState::Opened
+            && self.state_changed.elapsed() >= RESTART_UPTIME;
+
+        self.kill_gui();
+        self.set_state(GuiState::Closed);
+
+        if restart {
+            log::info!("Restarting GUI");
+            self.open_gui();
+        }
+    }
+
+    /// Sends a GUI that opened everything it shows.
+    fn gui_opened(&mut self, client: ClientId) {
+        self.open_guis.insert(client);
+        self.set_state(GuiState::Opened);
+
+        self.resync(client);
+        self.reply(client, GuiAction::Presets(self.persistent.presets.clone()));
+        self.reply(client, GuiAction::DoNotDisturb(self.persistent.dnd.clone()));
+        self.reply(
+            client,
+            GuiAction::Shortcuts(self.persistent.shortcuts.clone()),
+        );
+        self.reply(client, GuiAction::TimeFormat(self.persistent.time_format));
+        self.reply(
+            client,
+            GuiAction::RemovedOverrun(self.persistent.timers.removed_overrun()),
+        );
+    }
+
+    /// Handles a GUI closing, which leaves the GUI closed once every GUI has closed.
+    fn gui_closed(&mut self, client: ClientId) {
+        self.open_guis.remove(&client);
+        if self.open_guis.is_empty() {
+            self.set_state(GuiState::Closed);
+        }
+    }
 
-                self.state = GuiState::CloseRequested;
+    /// Handles a GUI reporting that it crashed, or the connection to it failing.
+    fn gui_crashed(&mut self, client: ClientId, reason: String) {
+        // The connection to a previous GUI can fail after a new one has been opened, & other connections, such as
+        // a replay, may never have opened.
+        if !self.open_guis.remove(&client)
+            || matches!(self.state, GuiState::OpenRequested | GuiState::Closed)
+        {
+            log::debug!("Connection to previous GUI closed: {reason}");
+            return;
+        }
+
+        log::error!("GUI crashed: {reason}");
+        if self.open_guis.is_empty() {
+            self.gui_lost();
+        }
+    }
+
+    /// Acts on a response from a connected GUI.
+    fn gui_response(&mut self, client: ClientId, response: GuiResponse) {
+        match response {
+            GuiResponse::Opened => self.gui_opened(client),
+            GuiResponse::Closed => self.gui_closed(client),
+            GuiResponse::Resync => self.resync(client),
+            GuiResponse::Crashed(reason) => self.gui_crashed(client, reason),
+            response => self.respond(response),
+        }
+    }
+
+    /// Acts on a change requested by a GUI, or by the tray itself.
+    fn respond(&mut self, response: GuiResponse) {
+        match response {
+            GuiResponse::Timer(command) => self.command(command),
+            GuiResponse::Presets(presets) => {
+                self.set_presets(presets);
+                self.save();
             }
-            GuiState::Closed => {
-                spawn_gui();
-                self.state = GuiState::OpenRequested;
+            GuiResponse::DoNotDisturb(dnd) => {
+                self.set_dnd(dnd);
+                self.save();
             }
-            GuiState::OpenRequested | GuiState::CloseRequested => {}
+            GuiResponse::Shortcuts(shortcuts) => {
+                self.set_shortcuts(shortcuts);
+                self.save();
+            }
+            GuiResponse::TimeFormat(format) => {
+                self.set_time_format(format);
+                self.save();
+            }
+            GuiResponse::ForgetOverrun => self.forget_overrun(),
+            GuiResponse::Opened
+            | GuiResponse::Closed
+            | GuiResponse::Resync
+            | GuiResponse::Crashed(_) => {
+                log::warn!("Ignoring a response that did not come from a GUI: {response:?}");
+            }
+        }
+    }
+
+    /// Reaps the GUI process once it exits, & kills a GUI that does not respond to the tray in time.
+    fn supervise_gui(&mut self) {
+        if let Some(child) = &mut self.gui {
+            match child.try_wait() {
+                Ok(None) => {}
+                Ok(Some(status)) => {
+                    self.gui = None;
+                    if self.state != GuiState::Closed {
+                        log::error!("GUI exited unexpectedly with {status}");
+                        return self.gui_lost();
+                    }
+                }
+                Err(err) => log::error!("Unable to check whether the GUI is running: {err}"),
+            }
+        }
+
+        let timeout = match self.state {
+            GuiState::OpenRequested => OPEN_TIMEOUT,
+            GuiState::CloseRequested => CLOSE_TIMEOUT,
+            GuiState::Opened | GuiState::Closed => return,
+        };
+
+        if self.state_changed.elapsed() >= timeout {
+            log::error!("GUI did not respond within {timeout:?}");
+            self.kill_gui();
+            self.set_state(GuiState::Closed);
+        }
+    }
+
+    /// The timers that have finished without the user acknowledging them.
+    fn unacknowledged(&self) -> Vec<TimerId> {
+        self.persistent
+            .timers
+            .iter()
+            .filter(|(_, timer)| timer.needs_attention())
+            .map(|(id, _)| id)
+            .collect()
+    }
+
+    /// Acknowledges every finished timer.
+    fn acknowledge_all(&mut self) {
+        for id in self.unacknowledged() {
+            self.command(TimerCommand::Acknowledge(id));
         }
     }
 
+    /// Pauses or resumes every timer.
+    fn pause_all(&mut self, pause: bool) {
+        let ids: Vec<_> = self
+            .persistent
+            .timers
+            .iter()
+            .filter(|(_, timer)| timer.paused() != pause)
+            .map(|(id, _)| id)
+            .collect();
+
+        for id in ids {
+            self.command(TimerCommand::Pause(id, pause));
+        }
+    }
+
+    /// Pauses every timer if any are running, otherwise resumes them all.
+    fn toggle_pause_all(&mut self) {
+        let any_running = self
+            .persistent
+            .timers
+            .iter()
+            .any(|(_, timer)| !timer.paused());
+        self.pause_all(any_running);
+    }
+
     /// Quits the Gui and the tray.
+    ///
+    /// The GUI is given time to save its state & close, after which it is killed. The tray saves its own state when
+    /// it stops.
     fn quit(&mut self) {
-        if let Err(err) = self.sender.send(GuiAction::Quit) {
-            log::error!("Internal tray communication was closed unexpectedly: {err}")
+        self.quitting = true;
+
+        match self.state {
+            GuiState::Opened => {
+                self.send(GuiAction::Quit);
+                self.set_state(GuiState::CloseRequested);
+            }
+            // The GUI has not connected yet, so it has nothing to save.
+            GuiState::OpenRequested => {
+                self.kill_gui();
+                self.set_state(GuiState::Closed);
+            }
+            GuiState::CloseRequested => {}
+            GuiState::Closed => self.set_state(GuiState::Closed),
         }
-        GLOBAL_CANCEL.cancel();
     }
 }
 
@@ -55,9 +740,48 @@ impl ksni::Tray for TimerTray {
         env!("CARGO_PKG_NAME").into()
     }
 
+    /// Left clicking the icon opens or closes the GUI.
+    fn activate(&mut self, _x: i32, _y: i32) {
+        self.toggle_gui();
+    }
+
+    /// Middle clicking the icon pauses or resumes every timer.
+    fn secondary_activate(&mut self, _x: i32, _y: i32) {
+        self.toggle_pause_all();
+    }
+
+    /// Scrolling over the icon lengthens or shortens the timer it shows.
+    fn scroll(&mut self, delta: i32, orientation: ksni::Orientation) {
+        if orientation != ksni::Orientation::Vertical || delta == 0 {
+            return;
+        }
+
+        let Some((id, _)) = self.displayed_timer() else {
+            return;
+        };
+
+        // Trays differ in how far a single step scrolls, so only the direction is used.
+        self.command(match delta > 0 {
+            true => TimerCommand::AddTime(id, SCROLL_STEP),
+            false => TimerCommand::RemoveTime(id, SCROLL_STEP),
+        });
+    }
+
     fn menu(&self) -> Vec<ksni::MenuItem<Self>> {
         use ksni::menu::*;
-        vec![
+
+        let any_running = self
+            .persistent
+            .timers
+            .iter()
+            .any(|(_, timer)| !timer.paused());
+        let any_paused = self
+            .persistent
+            .timers
+            .iter()
+            .any(|(_, timer)| timer.paused());
+
+        let mut menu = vec![
             CheckmarkItem {
                 label: "Gui".into(),
                 enabled: match self.state {
@@ -76,60 +800,1020 @@ impl ksni::Tray for TimerTray {
                 ..Default::default()
             }
             .into(),
+            CheckmarkItem {
+                label: "Do not disturb".into(),
+                checked: self.persistent.dnd.enabled,
+                activate: Box::new(Self::toggle_dnd),
+                ..Default::default()
+            }
+            .into(),
+            StandardItem {
+                label: match self.persistent.dnd.quiet_hours {
+                    Some(quiet_hours) => format!("Quiet hours {quiet_hours}"),
+                    None => String::new(),
+                },
+                visible: self.persistent.dnd.quiet_hours.is_some(),
+                enabled: false,
+                ..Default::default()
+            }
+            .into(),
+            MenuItem::Separator,
+            SubMenu {
+                label: "Start timer".into(),
+                submenu: self
+                    .persistent
+                    .presets
+                    .iter()
+                    .map(|preset| {
+                        let end_after = preset.end_after;
+                        StandardItem {
+                            label: format!(
+                                "{} ({})",
+                                preset.name,
+                                tray_format(self.persistent.time_format).total(end_after)
+                            ),
+                            activate: Box::new(move |tray: &mut Self| {
+                                tray.command(TimerCommand::Create(end_after))
+                            }),
+                            ..Default::default()
+                        }
+                        .into()
+                    })
+                    .chain([
+                        MenuItem::Separator,
+                        StandardItem {
+                            label: "Custom...".into(),
+                            activate: Box::new(Self::start_custom),
+                            ..Default::default()
+                        }
+                        .into(),
+                    ])
+                    .collect(),
+                ..Default::default()
+            }
+            .into(),
+            MenuItem::Separator,
+        ];
+
+        menu.extend(
+            self.persistent
+                .timers
+                .iter()
+                .enumerate()
+                .map(|(index, (id, timer))| {
+                    timer_menu(
+                        index,
+                        id,
+                        timer,
+                        self.persistent.pinned == Some(id),
+                        self.persistent.time_format,
+                    )
+                }),
+        );
+
+        let unacknowledged = self.unacknowledged().len();
+
+        menu.extend([
+            MenuItem::Separator,
+            SubMenu {
+                label: format!(
+                    "Missed whilst in do not disturb ({})",
+                    self.missed_history.len()
+                ),
+                visible: !self.quiet && !self.missed_history.is_empty(),
+                submenu: self
+                    .missed_history
+                    .iter()
+                    .map(|missed| {
+                        StandardItem {
+                            label: self.describe_missed(missed),
+                            enabled: false,
+                            ..Default::default()
+                        }
+                        .into()
+                    })
+                    .chain([
+                        MenuItem::Separator,
+                        StandardItem {
+                            label: "Dismiss".into(),
+                            activate: Box::new(|tray: &mut Self| tray.missed_history.clear()),
+                            ..Default::default()
+                        }
+                        .into(),
+                    ])
+                    .collect(),
+                ..Default::default()
+            }
+            .into(),
+            StandardItem {
+                label: format!("Acknowledge finished ({unacknowledged})"),
+                visible: unacknowledged > 0,
+                activate: Box::new(Self::acknowledge_all),
+                ..Default::default()
+            }
+            .into(),
+            StandardItem {
+                label: "Pause all".into(),
+                enabled: any_running,
+                activate: Box::new(|tray: &mut Self| tray.pause_all(true)),
+                ..Default::default()
+            }
+            .into(),
+            StandardItem {
+                label: "Resume all".into(),
+                enabled: any_paused,
+                activate: Box::new(|tray: &mut Self| tray.pause_all(false)),
+                ..Default::default()
+            }
+            .into(),
+            MenuItem::Separator,
             StandardItem {
                 label: "Quit".into(),
                 activate: Box::new(Self::quit),
                 ..Default::default()
             }
             .into(),
-        ]
+        ]);
+
+        menu
     }
 
     fn icon_pixmap(&self) -> Vec<ksni::Icon> {
-        let img = image::load_from_memory_with_format(
-            include_bytes!("icon.png"),
-            image::ImageFormat::Png,
-        )
-        .expect("'icon.png' is not a valid file");
+        self.icon.pixmaps()
+    }
+
+    fn status(&self) -> ksni::Status {
+        // Alerts are held back until do not disturb is turned off.
+        match self.quiet || self.unacknowledged().is_empty() {
+            true => ksni::Status::Active,
+            false => ksni::Status::NeedsAttention,
+        }
+    }
+
+    fn attention_icon_pixmap(&self) -> Vec<ksni::Icon> {
+        self.icon.attention_pixmaps()
+    }
+
+    fn title(&self) -> String {
+        let format = self.persistent.time_format;
+        match self.displayed_timer() {
+            Some((_, timer)) if timer.finished() && timer.overrun().is_zero() => {
+                format!("{APP_NAME} – finished")
+            }
+            Some((_, timer)) if timer.finished() => {
+                format!("{APP_NAME} – finished {}", format_remaining(timer, format))
+            }
+            Some((_, timer)) => format!("{APP_NAME} – {}", format_remaining(timer, format)),
+            None => APP_NAME.into(),
+        }
+    }
+
+    fn tool_tip(&self) -> ksni::ToolTip {
+        let now = SystemTime::now();
+
+        let mut description = self
+            .persistent
+            .timers
+            .iter()
+            .enumerate()
+            .map(|(index, (_, timer))| {
+                let status = timer_status(index, timer, self.persistent.time_format);
+                match timer.paused() || timer.finished() {
+                    true => status,
+                    false => format!(
+                        "{status}, ends at {}",
+                        LocalTime::at(now + timer.remaining())
+                    ),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let unacknowledged = self.unacknowledged().len();
+        if self.quiet {
+            description.push("Do not disturb".into());
+        } else if unacknowledged > 0 {
+            description.push(format!("{unacknowledged} finished, not acknowledged"));
+        }
+
+        if !self.quiet && !self.missed_history.is_empty() {
+            description.push(format!(
+                "{} finished whilst in do not disturb",
+                self.missed_history.len()
+            ));
+        }
+
+        let overrun = self.persistent.timers.recorded_overrun();
+        if !overrun.is_zero() {
+            description.push(format!(
+                "{} past their ends in total",
+                tray_format(self.persistent.time_format).overrun(overrun)
+            ));
+        }
+
+        ksni::ToolTip {
+            title: APP_NAME.into(),
+            description: match description.is_empty() {
+                true => "No timers".into(),
+                false => description.join("\n"),
+            },
+            ..Default::default()
+        }
+    }
+}
+
+/// Waits for the process to exit in the background, so the tray is not blocked.
+fn reap(mut child: Child) {
+    tokio::task::spawn_blocking(move || child.wait());
+}
+
+/// The name the user knows the timer by.
+///
+/// This is the label the user gave the timer, otherwise its position amongst the timers.
+fn timer_name(index: usize, timer: &TimerData) -> String {
+    timer.appearance().name(|| format!("Timer {}", index + 1))
+}
+
+/// A single line describing the state of the timer, with times in the given format unless the timer has its own.
+fn timer_status(index: usize, timer: &TimerData, format: TimeFormat) -> String {
+    let mut status = format!(
+        "{} – {}",
+        timer_name(index, timer),
+        format_remaining(timer, format)
+    );
+
+    if timer.finished() {
+        status.push_str(" (finished)");
+    } else if timer.paused() {
+        status.push_str(" (paused)");
+    }
+
+    status
+}
+
+/// The submenu for controlling a single timer.
+fn timer_menu(
+    index: usize,
+    id: TimerId,
+    timer: &TimerData,
+    pinned: bool,
+    format: TimeFormat,
+) -> ksni::MenuItem<TimerTray> {
+    use ksni::menu::*;
+
+    let paused = timer.paused();
+    let label = timer_status(index, timer, format);
+
+    let command = move |command: TimerCommand| -> Box<dyn Fn(&mut TimerTray) + Send> {
+        Box::new(move |tray| tray.command(command.clone()))
+    };
+
+    SubMenu {
+        label,
+        submenu: vec![
+            StandardItem {
+                label: "Acknowledge".into(),
+                visible: timer.needs_attention(),
+                activate: command(TimerCommand::Acknowledge(id)),
+                ..Default::default()
+            }
+            .into(),
+            StandardItem {
+                label: if paused { "Resume" } else { "Pause" }.into(),
+                activate: command(TimerCommand::Pause(id, !paused)),
+                ..Default::default()
+            }
+            .into(),
+            StandardItem {
+                label: "Reset".into(),
+                activate: command(TimerCommand::Reset(id)),
+                ..Default::default()
+            }
+            .into(),
+            StandardItem {
+                label: "+1 min".into(),
+                activate: command(TimerCommand::AddTime(id, Duration::from_secs(60))),
+                ..Default::default()
+            }
+            .into(),
+            StandardItem {
+                label: "+5 min".into(),
+                activate: command(TimerCommand::AddTime(id, Duration::from_secs(5 * 60))),
+                ..Default::default()
+            }
+            .into(),
+            CheckmarkItem {
+                label: "Pause when away".into(),
+                checked: timer.pause_when_away(),
+                activate: command(TimerCommand::PauseWhenAway(id, !timer.pause_when_away())),
+                ..Default::default()
+            }
+            .into(),
+            CheckmarkItem {
+                label: "Keep counting after it ends".into(),
+                checked: timer.overtime(),
+                activate: command(TimerCommand::Overtime(id, !timer.overtime())),
+                ..Default::default()
+            }
+            .into(),
+            keep_awake_menu(id, timer.keep_awake()),
+            CheckmarkItem {
+                label: "Show on icon".into(),
+                checked: pinned,
+                activate: Box::new(move |tray: &mut TimerTray| tray.toggle_pin(id)),
+                ..Default::default()
+            }
+            .into(),
+            MenuItem::Separator,
+            StandardItem {
+                label: "Delete".into(),
+                activate: command(TimerCommand::Remove(id)),
+                ..Default::default()
+            }
+            .into(),
+        ],
+        ..Default::default()
+    }
+    .into()
+}
+
+/// The submenu for choosing when a timer keeps the computer awake.
+fn keep_awake_menu(id: TimerId, keep_awake: KeepAwake) -> ksni::MenuItem<TimerTray> {
+    use ksni::menu::*;
+
+    // The final minutes chosen in the GUI are kept, otherwise the last few minutes are offered.
+    let window = match keep_awake {
+        KeepAwake::Final(window) => window,
+        _ => KEEP_AWAKE_WINDOW,
+    };
+    let options = [
+        KeepAwake::Never,
+        KeepAwake::WhileRunning,
+        KeepAwake::Final(window),
+    ];
+
+    SubMenu {
+        label: "Keep computer awake".into(),
+        submenu: vec![
+            RadioGroup {
+                selected: options
+                    .iter()
+                    .position(|option| *option == keep_awake)
+                    .unwrap_or_default(),
+                select: Box::new(move |tray: &mut TimerTray, index| {
+                    tray.command(TimerCommand::KeepAwake(id, options[index]))
+                }),
+                options: vec![
+                    RadioItem {
+                        label: "Never".into(),
+                        ..Default::default()
+                    },
+                    RadioItem {
+                        label: "Whilst running".into(),
+                        ..Default::default()
+                    },
+                    RadioItem {
+                        label: format!("For the final {} min", window.as_secs() / 60),
+                        ..Default::default()
+                    },
+                ],
+            }
+            .into(),
+        ],
+        ..Default::default()
+    }
+    .into()
+}
+
+/// Formats the time remaining on a timer, in the format the user chose for it, otherwise the given format.
+fn format_remaining(timer: &TimerData, format: TimeFormat) -> String {
+    tray_format(timer.time_format().unwrap_or(format)).remaining(timer)
+}
+
+/// The format to show times in the tray, which only updates each second, too slowly for tenths of a second.
+fn tray_format(format: TimeFormat) -> TimeFormat {
+    match format {
+        TimeFormat::Tenths => TimeFormat::Clock,
+        format => format,
+    }
+}
+
+/// Regularly ticks the timers so the tray shows how much time is remaining.
+pub(crate) async fn tick_tray(handle: TrayHandle) {
+    let mut interval = tokio::time::interval(TICK_INTERVAL);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+    loop {
+        until_global_cancel!(interval.tick());
+        until_global_cancel!(handle.update(|tray| {
+            tray.persistent.timers.tick();
+            tray.record_finished();
+            tray.check_dnd();
+            tray.refresh_icon();
+            tray.check_keep_awake();
+            tray.supervise_gui();
+        }));
+    }
+}
+
+/// Pauses or resumes timers as the user leaves or returns to their computer.
+pub(crate) async fn watch_presence(handle: TrayHandle, mut presence: UnboundedReceiver<Presence>) {
+    // The watcher stops if the buses are unavailable, which leaves the timers running as usual.
+    while let Some(presence) = until_global_cancel!(presence.recv()) {
+        until_global_cancel!(handle.update(|tray| tray.set_presence(presence)));
+    }
+}
+
+/// Performs the actions of the keyboard shortcuts the user presses.
+pub(crate) async fn watch_shortcuts(
+    handle: TrayHandle,
+    mut shortcuts: UnboundedReceiver<Shortcut>,
+) {
+    while let Some(shortcut) = until_global_cancel!(shortcuts.recv()) {
+        until_global_cancel!(handle.update(|tray| tray.shortcut(shortcut)));
+    }
+}
 
-        let (width, height) = img.dimensions();
-        let mut data = img.into_rgba8().into_vec();
+/// Quits, reloads or opens & closes the GUI when the tray is sent a signal.
+///
+/// SIGTERM & SIGINT quit once the GUI has saved & closed, unless they are sent again. SIGHUP reloads the
+/// configuration, & SIGUSR1 opens or closes the GUI.
+///
+/// The signals are listened for before this returns, so none sent afterwards are missed.
+pub(crate) fn watch_signals(handle: TrayHandle) -> impl Future<Output = ()> {
+    enum Signal {
+        Quit,
+        Reload,
+        ToggleGui,
+    }
+
+    // Each signal is listened for on its own, so the others still work if one cannot be.
+    let listen = |kind: SignalKind| {
+        signal(kind)
+            .inspect_err(|err| {
+                log::error!("Unable to listen for signal {}: {err}", kind.as_raw_value())
+            })
+            .ok()
+    };
+    let mut terminate = listen(SignalKind::terminate());
+    let mut interrupt = listen(SignalKind::interrupt());
+    let mut hangup = listen(SignalKind::hangup());
+    let mut user = listen(SignalKind::user_defined1());
 
-        assert_eq!(data.len() % 4, 0);
+    async move {
+        let mut quitting = false;
+        loop {
+            let signal = until_global_cancel!(async {
+                tokio::select! {
+                    Some(()) = received(&mut terminate) => Some(Signal::Quit),
+                    Some(()) = received(&mut interrupt) => Some(Signal::Quit),
+                    Some(()) = received(&mut hangup) => Some(Signal::Reload),
+                    Some(()) = received(&mut user) => Some(Signal::ToggleGui),
+                    else => None,
+                }
+            });
 
-        for pixel in data.chunks_exact_mut(4) {
-            pixel.rotate_right(1) // rgba to argb
+            match signal {
+                Some(Signal::Quit) if quitting => {
+                    log::warn!("Quitting without waiting for the GUI to close");
+                    until_global_cancel!(handle.update(TimerTray::kill_gui));
+                    GLOBAL_CANCEL.cancel();
+                }
+                Some(Signal::Quit) => {
+                    log::info!("Quitting");
+                    quitting = true;
+                    until_global_cancel!(handle.update(TimerTray::quit));
+                }
+                Some(Signal::Reload) => {
+                    until_global_cancel!(handle.update(TimerTray::reload));
+                }
+                Some(Signal::ToggleGui) => {
+                    until_global_cancel!(handle.update(TimerTray::toggle_gui));
+                }
+                None => return,
+            }
         }
+    }
+}
 
-        vec![ksni::Icon {
-            width: width as i32,
-            height: height as i32,
-            data,
-        }]
+/// Waits for the signal to be received, or forever if it could not be listened for.
+async fn received(signal: &mut Option<tokio::signal::unix::Signal>) -> Option<()> {
+    match signal {
+        Some(signal) => signal.recv().await,
+        None => std::future::pending().await,
     }
 }
 
 pub(crate) async fn update_tray(
-    handle: Handle<TimerTray>,
-    mut rx_from_gui: UnboundedReceiver<GuiResponse>,
+    handle: TrayHandle,
+    mut rx_from_gui: UnboundedReceiver<(ClientId, GuiResponse)>,
+    mut responses: UnboundedReceiver<GuiResponse>,
 ) {
     loop {
-        let response = match until_global_cancel!(rx_from_gui.recv()) {
-            Some(response) => response,
-            None => {
-                log::error!("Internal tray communication was closed unexpectedly");
-                GLOBAL_CANCEL.cancel();
-                break;
+        let received = until_global_cancel!(async {
+            tokio::select! {
+                Some((client, response)) = rx_from_gui.recv() => Some((Some(client), response)),
+                Some(response) = responses.recv() => Some((None, response)),
+                else => None,
             }
+        });
+        let Some((client, response)) = received else {
+            log::error!("Internal tray communication was closed unexpectedly");
+            GLOBAL_CANCEL.cancel();
+            break;
         };
 
-        until_global_cancel!(handle.update(|tray| {
-            tray.state = match response {
-                GuiResponse::Closed => GuiState::Closed,
-                GuiResponse::Opened => GuiState::Opened,
-            };
+        until_global_cancel!(handle.update(|tray| match client {
+            Some(client) => tray.gui_response(client, response),
+            None => tray.respond(response),
         }));
 
         log::debug!("Tray tick loop.");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use ksni::{MenuItem, Tray as _};
+    use tokio::sync::{broadcast, mpsc, watch};
+
+    use super::{APP_NAME, SCROLL_STEP, TaskSenders, TimerTray, watch_signals};
+    use crate::{
+        comms::{
+            GuiAction, GuiResponse, TransportKind,
+            timer_sync::{TimerCommand, TimerId},
+        },
+        format::TimeFormat,
+        shortcuts::Shortcuts,
+        timer::Preset,
+        tray::{
+            GLOBAL_CANCEL, GuiLauncher, GuiState, TrayOptions,
+            comms::{BACKLOG, ClientId},
+            handle::TrayHandle,
+            icon::IconState,
+            storage::Persistent,
+        },
+    };
+
+    /// Activates the menu item with the last label, in the submenus with the labels before it.
+    ///
+    /// Items are found by the start of their label, as they may include the time remaining.
+    fn activate(tray: &mut TimerTray, path: &[&str]) {
+        let (item, submenus) = path.split_last().expect("An item is given");
+
+        let mut menu = tray.menu();
+        for label in submenus {
+            menu = menu
+                .into_iter()
+                .find_map(|item| match item {
+                    MenuItem::SubMenu(submenu) if submenu.label.starts_with(label) => {
+                        Some(submenu.submenu)
+                    }
+                    _ => None,
+                })
+                .unwrap_or_else(|| panic!("No submenu labelled {label}"));
+        }
+
+        let activate = menu
+            .into_iter()
+            .find_map(|found| match found {
+                MenuItem::Standard(found) if found.label.starts_with(item) => Some(found.activate),
+                MenuItem::Checkmark(found) if found.label.starts_with(item) => Some(found.activate),
+                _ => None,
+            })
+            .unwrap_or_else(|| panic!("No item labelled {item}"));
+        activate(tray);
+    }
+
+    /// A tray with the given state, along with the keyboard shortcuts it binds.
+    fn tray(persistent: Persistent) -> (TimerTray, watch::Receiver<Shortcuts>) {
+        let (sender, _) = broadcast::channel(BACKLOG);
+        let (responses, _) = mpsc::unbounded_channel();
+        let (shortcuts, bound) = watch::channel(Shortcuts::default());
+        let tasks = TaskSenders {
+            keep_awake: watch::channel(false).0,
+            player: watch::channel(None).0,
+            shortcuts,
+            notifications: mpsc::unbounded_channel().0,
+        };
+        let launcher = GuiLauncher {
+            transport: TransportKind::Tcp,
+            pipes: None,
+        };
+        let options = TrayOptions {
+            transport: TransportKind::Tcp,
+            icons: None,
+            restart_gui: false,
+            daemon: true,
+            open_gui: false,
+            listeners: Vec::new(),
+        };
+
+        let mut tray = TimerTray::new(sender, responses, launcher, tasks, persistent, &options);
+        tray.start();
+        (tray, bound)
+    }
+
+    #[test]
+    fn reloading_settings() {
+        let mut current = Persistent::default();
+        let _ = current
+            .timers
+            .apply(TimerCommand::Create(Duration::from_secs(60)));
+        let (mut tray, mut bound) = tray(current);
+        bound.mark_unchanged();
+
+        // Reloading unchanged shortcuts does not bind them again.
+        tray.reload_from(Persistent::default());
+        assert!(!bound.has_changed().expect("Tray is running"));
+
+        let mut saved = Persistent {
+            presets: vec![Preset {
+                name: "Eggs".into(),
+                end_after: Duration::from_secs(6 * 60),
+            }],
+            time_format: TimeFormat::Compact,
+            ..Default::default()
+        };
+        saved.dnd.enabled = true;
+        saved.shortcuts.pause_all = "CTRL+SHIFT+p".into();
+        tray.reload_from(saved.clone());
+
+        assert_eq!(tray.persistent.presets, saved.presets);
+        assert_eq!(tray.persistent.dnd, saved.dnd);
+        assert!(tray.quiet);
+        assert_eq!(tray.persistent.shortcuts, saved.shortcuts);
+        assert!(bound.has_changed().expect("Tray is running"));
+        assert_eq!(*bound.borrow_and_update(), saved.shortcuts);
+        assert_eq!(tray.persistent.time_format, TimeFormat::Compact);
+
+        // The tray's own timers are kept.
+        assert_eq!(tray.persistent.timers.iter().count(), 1);
+    }
+
+    #[test]
+    fn several_guis() {
+        let (mut tray, _) = tray(Persistent::default());
+        let (first, second, replay) = (ClientId(0), ClientId(1), ClientId(2));
+
+        tray.gui_opened(first);
+        tray.gui_opened(second);
+        assert!(tray.state == GuiState::Opened);
+
+        // A connection that never opened a GUI does not close the others.
+        tray.gui_crashed(replay, "Connection reset".into());
+        assert!(tray.state == GuiState::Opened);
+
+        tray.gui_closed(first);
+        assert!(tray.state == GuiState::Opened);
+        tray.gui_closed(second);
+        assert!(tray.state == GuiState::Closed);
+    }
+
+    #[test]
+    fn menu_actions() {
+        let (mut tray, _) = tray(Persistent::default());
+        activate(&mut tray, &["Start timer", "Tea"]);
+        activate(&mut tray, &["Start timer", "Focus"]);
+
+        let timer = |tray: &TimerTray, index: usize| {
+            let (_, timer) = tray
+                .persistent
+                .timers
+                .iter()
+                .nth(index)
+                .expect("Timer was started");
+            timer.clone()
+        };
+        assert_eq!(timer(&tray, 0).end_after(), Duration::from_secs(4 * 60));
+        assert_eq!(timer(&tray, 1).end_after(), Duration::from_secs(25 * 60));
+
+        activate(&mut tray, &["Timer 1", "+5 min"]);
+        assert_eq!(timer(&tray, 0).end_after(), Duration::from_secs(9 * 60));
+        activate(&mut tray, &["Timer 1", "Pause"]);
+        assert!(timer(&tray, 0).paused());
+        assert!(!timer(&tray, 1).paused());
+        activate(&mut tray, &["Timer 1", "Resume"]);
+        assert!(!timer(&tray, 0).paused());
+
+        activate(&mut tray, &["Pause all"]);
+        assert!(timer(&tray, 0).paused() && timer(&tray, 1).paused());
+        activate(&mut tray, &["Resume all"]);
+        assert!(!timer(&tray, 0).paused() && !timer(&tray, 1).paused());
+
+        activate(&mut tray, &["Timer 2", "Delete"]);
+        assert_eq!(tray.persistent.timers.iter().count(), 1);
+    }
+
+    #[test]
+    fn icon_shows_soonest_timer() {
+        let mut persistent = Persistent::default();
+        for minutes in [10, 5, 1] {
+            let _ = persistent
+                .timers
+                .apply(TimerCommand::Create(Duration::from_secs(minutes * 60)));
+        }
+        let _ = persistent
+            .timers
+            .apply(TimerCommand::Pause(TimerId(2), true));
+        let (mut tray, _) = tray(persistent);
+        let displayed = |tray: &TimerTray| tray.displayed_timer().map(|(id, _)| id);
+
+        // The running timer that ends soonest is shown before any paused timer.
+        assert_eq!(displayed(&tray), Some(TimerId(1)));
+
+        // Unless the user pins another timer.
+        activate(&mut tray, &["Timer 1", "Show on icon"]);
+        assert_eq!(displayed(&tray), Some(TimerId(0)));
+        activate(&mut tray, &["Timer 1", "Show on icon"]);
+        assert_eq!(displayed(&tray), Some(TimerId(1)));
+
+        // Finished timers are shown before paused timers, which are shown by how close they are to ending.
+        activate(&mut tray, &["Pause all"]);
+        assert_eq!(displayed(&tray), Some(TimerId(2)));
+        tray.command(TimerCommand::Create(Duration::ZERO));
+        let (id, timer) = tray.displayed_timer().expect("Timers exist");
+        assert_eq!(id, TimerId(3));
+        assert_eq!(IconState::of(timer), IconState::Finished);
+
+        // Deleting a pinned timer unpins it.
+        activate(&mut tray, &["Timer 1", "Show on icon"]);
+        activate(&mut tray, &["Timer 1", "Delete"]);
+        assert_eq!(tray.persistent.pinned, None);
+    }
+
+    #[test]
+    fn finished_timers_need_attention() {
+        let mut persistent = Persistent::default();
+        let _ = persistent
+            .timers
+            .apply(TimerCommand::Create(Duration::from_secs(60)));
+        let (mut tray, _) = tray(persistent);
+        assert_eq!(tray.status(), ksni::Status::Active);
+        assert!(tray.title().ends_with("01:00"));
+
+        tray.command(TimerCommand::Create(Duration::ZERO));
+        tray.command(TimerCommand::Pause(TimerId(0), true));
+        assert_eq!(tray.status(), ksni::Status::NeedsAttention);
+        assert_eq!(tray.title(), format!("{APP_NAME} – finished"));
+        assert!(
+            tray.tool_tip()
+                .description
+                .contains("1 finished, not acknowledged")
+        );
+
+        // Alerts are held back whilst in do not disturb.
+        activate(&mut tray, &["Do not disturb"]);
+        assert_eq!(tray.status(), ksni::Status::Active);
+        activate(&mut tray, &["Do not disturb"]);
+        assert_eq!(tray.status(), ksni::Status::NeedsAttention);
+
+        activate(&mut tray, &["Acknowledge finished"]);
+        assert_eq!(tray.status(), ksni::Status::Active);
+        assert!(!tray.tool_tip().description.contains("not acknowledged"));
+    }
+
+    #[test]
+    fn time_formats() {
+        let mut persistent = Persistent {
+            time_format: TimeFormat::Compact,
+            ..Default::default()
+        };
+        let _ = persistent
+            .timers
+            .apply(TimerCommand::Create(Duration::from_secs(90)));
+        let _ = persistent
+            .timers
+            .apply(TimerCommand::Pause(TimerId(0), true));
+        let (mut tray, _) = tray(persistent);
+        assert!(tray.title().ends_with("1m 30s"));
+
+        // The tray only updates each second, so shows whole seconds instead of tenths.
+        tray.set_time_format(TimeFormat::Tenths);
+        assert!(tray.title().ends_with("00:01:30"));
+        tray.command(TimerCommand::TimeFormat(
+            TimerId(0),
+            Some(TimeFormat::Tenths),
+        ));
+        tray.set_time_format(TimeFormat::Compact);
+        assert!(tray.title().ends_with("00:01:30"));
+
+        // Presets are shown in the user's format.
+        activate(&mut tray, &["Start timer", "Tea (4m)"]);
+        assert_eq!(tray.persistent.timers.iter().count(), 2);
+    }
+
+    #[test]
+    fn gestures() {
+        let mut persistent = Persistent::default();
+        for minutes in [10, 5] {
+            let _ = persistent
+                .timers
+                .apply(TimerCommand::Create(Duration::from_secs(minutes * 60)));
+        }
+        let (mut tray, _) = tray(persistent);
+        let end_after = |tray: &TimerTray, id: TimerId| {
+            let (_, timer) = tray
+                .persistent
+                .timers
+                .iter()
+                .find(|(other, _)| *other == id)
+                .expect("Timer exists");
+            timer.end_after()
+        };
+
+        // Scrolling changes the timer shown on the icon by a single step, however far the tray scrolled.
+        tray.scroll(3, ksni::Orientation::Vertical);
+        assert_eq!(
+            end_after(&tray, TimerId(1)),
+            Duration::from_secs(5 * 60) + SCROLL_STEP
+        );
+        tray.scroll(-1, ksni::Orientation::Vertical);
+        tray.scroll(-1, ksni::Orientation::Vertical);
+        assert_eq!(
+            end_after(&tray, TimerId(1)),
+            Duration::from_secs(5 * 60) - SCROLL_STEP
+        );
+        tray.scroll(1, ksni::Orientation::Horizontal);
+        assert_eq!(
+            end_after(&tray, TimerId(1)),
+            Duration::from_secs(5 * 60) - SCROLL_STEP
+        );
+        assert_eq!(end_after(&tray, TimerId(0)), Duration::from_secs(10 * 60));
+
+        // Middle clicking pauses every timer if any are running, otherwise resumes them.
+        tray.command(TimerCommand::Pause(TimerId(0), true));
+        tray.secondary_activate(0, 0);
+        assert!(
+            tray.persistent
+                .timers
+                .iter()
+                .all(|(_, timer)| timer.paused())
+        );
+        tray.secondary_activate(0, 0);
+        assert!(
+            tray.persistent
+                .timers
+                .iter()
+                .all(|(_, timer)| !timer.paused())
+        );
+    }
+
+    #[test]
+    fn missed_timers() {
+        let (mut tray, _) = tray(Persistent::default());
+        let (notifications, mut shown) = mpsc::unbounded_channel();
+        tray.notifications = notifications;
+
+        // Each period of do not disturb only tells the user of the timers missed during it.
+        for period in 1..=2 {
+            activate(&mut tray, &["Do not disturb"]);
+            tray.command(TimerCommand::Create(Duration::ZERO));
+            assert!(shown.try_recv().is_err());
+            activate(&mut tray, &["Do not disturb"]);
+
+            let notification = shown.try_recv().expect("Missed timers are shown");
+            assert_eq!(notification.summary, "Missed whilst in do not disturb (1)");
+            assert!(
+                notification
+                    .body
+                    .starts_with(&format!("Timer {period} finished at"))
+            );
+            assert!(!notification.body.contains('\n'));
+        }
+
+        // Ending do not disturb again with nothing missed shows nothing.
+        activate(&mut tray, &["Do not disturb"]);
+        activate(&mut tray, &["Do not disturb"]);
+        assert!(shown.try_recv().is_err());
+
+        // The menu keeps every timer missed until the user dismisses them.
+        assert_eq!(tray.missed_history.len(), 2);
+        activate(&mut tray, &["Missed whilst in do not disturb", "Dismiss"]);
+        assert!(tray.missed_history.is_empty());
+    }
+
+    #[test]
+    fn forgetting_overrun() {
+        let mut persistent = Persistent::default();
+        let overrunning = ron::from_str(
+            "(duration: (secs: 90, nanos: 0), end_after: (secs: 60, nanos: 0), paused: true, \
+            overtime: true)",
+        )
+        .expect("Timer is valid");
+        let _ = persistent.timers.create(overrunning);
+        let (mut tray, _) = tray(persistent);
+        let mut sent = tray.sender.subscribe();
+        let mut removed_overrun = move || {
+            std::iter::from_fn(|| sent.try_recv().ok())
+                .filter_map(|outgoing| match outgoing.action {
+                    GuiAction::RemovedOverrun(overrun) => Some(overrun),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // GUIs are told the overrun of removed timers, as they no longer have the timers.
+        tray.command(TimerCommand::Remove(TimerId(0)));
+        assert_eq!(removed_overrun(), [Duration::from_secs(30)]);
+        assert!(
+            tray.tool_tip()
+                .description
+                .contains("00:00:30 past their ends")
+        );
+
+        tray.respond(GuiResponse::ForgetOverrun);
+        assert_eq!(removed_overrun(), [Duration::ZERO]);
+        assert!(!tray.tool_tip().description.contains("past their ends"));
+    }
+
+    /// Raises the signal in the tray's process.
+    fn raise(signal: libc::c_int) {
+        // SAFETY: Raising a signal has no requirements, & the tray is listening for each signal raised.
+        assert_eq!(unsafe { libc::raise(signal) }, 0);
+    }
+
+    /// Waits for the tray to handle a signal it was sent.
+    async fn handled(handle: &TrayHandle, done: impl Fn(&TimerTray) -> bool) {
+        for _ in 0..100 {
+            if handle.update(|tray| done(tray)).await == Some(true) {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        panic!("The tray did not handle the signal");
+    }
+
+    #[tokio::test]
+    async fn signals() {
+        let (mut tray, _) = tray(Persistent::default());
+        tray.gui_opened(ClientId(0));
+        let mut track = tray.player.subscribe();
+        let handle = TrayHandle::headless(tray);
+        let watching = tokio::spawn(watch_signals(handle.clone()));
+
+        // SIGUSR1 closes the open GUI.
+        raise(libc::SIGUSR1);
+        handled(&handle, |tray| tray.state == GuiState::CloseRequested).await;
+
+        // SIGHUP reloads the icon, rather than stopping the tray.
+        track.mark_unchanged();
+        raise(libc::SIGHUP);
+        tokio::time::timeout(Duration::from_secs(5), track.changed())
+            .await
+            .expect("The tray reloaded")
+            .expect("Tray is running");
+
+        // SIGTERM waits for the GUI to close before quitting.
+        raise(libc::SIGTERM);
+        handled(&handle, |tray| tray.quitting).await;
+        assert!(!GLOBAL_CANCEL.is_cancelled());
+
+        watching.abort();
+    }
+
+    /// Set when [`quitting_twice`] runs in its own process.
+    const QUITTING_TWICE: &str = "GUI_TIMER_TEST_QUITTING_TWICE";
+
+    #[test]
+    fn quitting_twice() {
+        let status = std::process::Command::new(std::env::current_exe().expect("Test has a path"))
+            .args([
+                "tray::tray_icon::tests::quitting_twice_in_process",
+                "--exact",
+                "--ignored",
+            ])
+            .env(QUITTING_TWICE, "1")
+            .stdout(std::process::Stdio::null())
+            .status()
+            .expect("Can run test");
+        assert!(status.success());
+    }
+
+    /// Sending SIGINT after SIGTERM quits without waiting for the GUI.
+    ///
+    /// This stops every task in the process, so it is only run in a process of its own by [`quitting_twice`].
+    #[tokio::test]
+    #[ignore = "Run in its own process by quitting_twice"]
+    async fn quitting_twice_in_process() {
+        if std::env::var_os(QUITTING_TWICE).is_none() {
+            return;
+        }
+
+        let (mut tray, _) = tray(Persistent::default());
+        tray.gui_opened(ClientId(0));
+        let handle = TrayHandle::headless(tray);
+        let watching = tokio::spawn(watch_signals(handle.clone()));
+
+        raise(libc::SIGTERM);
+        handled(&handle, |tray| tray.quitting).await;
+        assert!(!GLOBAL_CANCEL.is_cancelled());
+
+        raise(libc::SIGINT);
+        tokio::time::timeout(Duration::from_secs(5), GLOBAL_CANCEL.cancelled())
+            .await
+            .expect("The tray quit");
+        watching.await.expect("Task did not panic");
+    }
+}